@@ -5,4 +5,5 @@ mkdir $TARGET_DIR
 
 cp LICENSE $TARGET_DIR
 cp manifest.json $TARGET_DIR
+cp -r js $TARGET_DIR
 cp target/debug/mock-addon $TARGET_DIR
@@ -0,0 +1,49 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.*
+ */
+
+use async_trait::async_trait;
+use gateway_addon_rust::action::{Action, ActionDescription, ActionHandle};
+
+/// An action which starts when requested and stays pending until it is cancelled.
+pub struct MockAction {
+    name: String,
+    description: webthings_gateway_ipc_types::Action,
+}
+
+impl MockAction {
+    pub fn new(name: String, description: webthings_gateway_ipc_types::Action) -> Self {
+        Self { name, description }
+    }
+}
+
+#[async_trait]
+impl Action for MockAction {
+    type Input = serde_json::Value;
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> ActionDescription<Self::Input> {
+        let mut description = ActionDescription::default();
+        description.description = self.description.description.clone();
+        description.input = self.description.input.clone();
+        description.links = self.description.links.clone();
+        description.title = self.description.title.clone();
+        description
+    }
+
+    async fn perform(
+        &mut self,
+        mut action_handle: ActionHandle<Self::Input>,
+    ) -> Result<(), String> {
+        action_handle.start().await.map_err(|err| err.to_string())
+    }
+
+    async fn cancel(&mut self, _action_id: String) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.*
+ */
+
+use async_trait::async_trait;
+use gateway_addon_rust::api_handler::{ApiHandler, ApiRequest, ApiResponse};
+use serde_json::json;
+
+/// Answers every request with a JSON description of the request.
+pub struct EchoApiHandler;
+
+#[async_trait]
+impl ApiHandler for EchoApiHandler {
+    async fn handle_request(&mut self, request: ApiRequest) -> Result<ApiResponse, String> {
+        let content = json!({
+            "method": request.method,
+            "path": request.path,
+            "query": request.query,
+            "body": request.body,
+        });
+        Ok(ApiResponse {
+            status: 200,
+            content_type: Some("application/json".to_owned()),
+            content: Some(content.to_string()),
+        })
+    }
+}
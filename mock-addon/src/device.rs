@@ -4,8 +4,10 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/.*
  */
 
-use crate::property::MockPropertyBuilder;
-use gateway_addon_rust::{Device, DeviceBuilder, DeviceDescription, DeviceHandle, Properties};
+use crate::{action::MockAction, property::MockPropertyBuilder};
+use gateway_addon_rust::{
+    Actions, Device, DeviceBuilder, DeviceDescription, DeviceHandle, Properties,
+};
 
 pub struct MockDeviceBuilder(webthings_gateway_ipc_types::Device);
 
@@ -47,6 +49,16 @@ impl DeviceBuilder for MockDeviceBuilder {
         properties
     }
 
+    fn actions(&self) -> Actions {
+        let mut actions: Actions = Vec::new();
+        if let Some(action_descriptions) = &self.0.actions {
+            for (name, description) in action_descriptions {
+                actions.push(Box::new(MockAction::new(name.clone(), description.clone())))
+            }
+        }
+        actions
+    }
+
     fn build(self, device_handle: DeviceHandle) -> Self::Device {
         MockDevice::new(device_handle)
     }
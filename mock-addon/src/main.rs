@@ -4,12 +4,20 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/.*
  */
 
+mod action;
 mod adapter;
+mod api_handler;
 mod control_socket;
 mod device;
+mod notifier;
 mod property;
 
-use crate::{adapter::MockAdapter, control_socket::ControlSocket};
+use crate::{
+    adapter::MockAdapter,
+    api_handler::EchoApiHandler,
+    control_socket::ControlSocket,
+    notifier::{MockNotifier, MockOutletBuilder},
+};
 use gateway_addon_rust::{api_error::ApiError, plugin::connect};
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
@@ -38,6 +46,22 @@ async fn run() -> Result<(), ApiError> {
         })
         .await?;
 
+    let notifier = plugin
+        .create_notifier(
+            &MockNotifier::id(),
+            &MockNotifier::name(),
+            |notifier_handle| MockNotifier::new(notifier_handle),
+        )
+        .await?;
+    notifier
+        .lock()
+        .await
+        .notifier_handle_mut()
+        .add_outlet(MockOutletBuilder)
+        .await?;
+
+    plugin.set_api_handler(EchoApiHandler).await?;
+
     let control_socket = ControlSocket::new(adapter.clone());
 
     tokio::join!(control_socket.event_loop(), plugin.event_loop());
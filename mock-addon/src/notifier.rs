@@ -0,0 +1,70 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.*
+ */
+
+use async_trait::async_trait;
+use gateway_addon_rust::{
+    notifier::{Notifier, NotifierHandle},
+    outlet::{Level, Outlet, OutletBuilder, OutletDescription, OutletHandle},
+};
+
+pub struct MockNotifier {
+    notifier_handle: NotifierHandle,
+}
+
+impl MockNotifier {
+    pub fn id() -> String {
+        String::from("mock-notifier")
+    }
+
+    pub fn name() -> String {
+        String::from("Mock notifier")
+    }
+
+    pub fn new(notifier_handle: NotifierHandle) -> Self {
+        MockNotifier { notifier_handle }
+    }
+}
+
+impl Notifier for MockNotifier {
+    fn notifier_handle_mut(&mut self) -> &mut NotifierHandle {
+        &mut self.notifier_handle
+    }
+}
+
+pub struct MockOutletBuilder;
+
+impl OutletBuilder for MockOutletBuilder {
+    type Outlet = MockOutlet;
+
+    fn id(&self) -> String {
+        String::from("mock-outlet")
+    }
+
+    fn description(&self) -> OutletDescription {
+        OutletDescription {
+            name: String::from("Mock outlet"),
+        }
+    }
+
+    fn build(self, outlet_handle: OutletHandle) -> Self::Outlet {
+        MockOutlet(outlet_handle)
+    }
+}
+
+/// An outlet which accepts every notification.
+pub struct MockOutlet(OutletHandle);
+
+#[async_trait]
+impl Outlet for MockOutlet {
+    fn outlet_handle_mut(&mut self) -> &mut OutletHandle {
+        &mut self.0
+    }
+
+    async fn notify(&mut self, title: String, message: String, level: Level) -> Result<(), String> {
+        log::info!("Notification {:?}: {} ({:?})", title, message, level);
+        Ok(())
+    }
+}
@@ -5,11 +5,15 @@
 use crate::{
     device::Device,
     macros::send,
+    model::ActionRequest,
     things_socket::{ConnectedMessage, ThingsMessage, ThingsMessages, ThingsSocket},
 };
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
-use webthings_gateway_ipc_types::{Device as DeviceDescription, Property as PropertyDescription};
+use webthings_gateway_ipc_types::{
+    ActionDescription, Device as DeviceDescription, EventDescription,
+    Property as PropertyDescription,
+};
 
 pub struct Adapter {
     id: String,
@@ -24,6 +28,14 @@ impl Adapter {
         }
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn has_device(&self, device_id: &str) -> bool {
+        self.devices.contains_key(device_id)
+    }
+
     pub fn add_device(&mut self, description: DeviceDescription) {
         let id = description.id.clone();
         let device = Device::new(description);
@@ -39,6 +51,12 @@ impl Adapter {
         }
     }
 
+    pub fn remove_device(&mut self, device_id: &str) {
+        if self.devices.remove(device_id).is_some() {
+            info!("Device {} of adapter {} removed", device_id, self.id);
+        }
+    }
+
     pub async fn update_property(
         &mut self,
         device_id: String,
@@ -49,6 +67,11 @@ impl Adapter {
         device.update_property(property).await
     }
 
+    pub async fn notify_event(&mut self, device_id: &str, event: EventDescription) -> Result<()> {
+        let device = self.get_device(device_id)?;
+        device.notify_event(event).await
+    }
+
     pub async fn set_connect_state(&mut self, device_id: String, state: bool) -> Result<()> {
         let device = self.get_device(&device_id)?;
         device.set_connect_state(state);
@@ -60,6 +83,39 @@ impl Adapter {
         Ok(())
     }
 
+    pub async fn add_action(&mut self, device_id: &str, action: ActionRequest) -> Result<()> {
+        let device = self.get_device(device_id)?;
+        device.add_action(action).await
+    }
+
+    pub async fn update_action(
+        &mut self,
+        device_id: &str,
+        action: ActionDescription,
+    ) -> Result<()> {
+        let device = self.get_device(device_id)?;
+        device.update_action(action).await
+    }
+
+    pub async fn fail_action(&mut self, device_id: &str, action_id: &str) -> Result<()> {
+        let device = self.get_device(device_id)?;
+        device.fail_action(action_id).await
+    }
+
+    pub fn remove_action(&mut self, device_id: &str, action_id: &str) -> Result<ActionRequest> {
+        let device = self.get_device(device_id)?;
+        device
+            .remove_action(action_id)
+            .ok_or_else(|| anyhow!("Device {} has no action request {}", device_id, action_id))
+    }
+
+    pub fn actions(&self, device_id: &str) -> Result<Vec<ActionRequest>> {
+        self.devices
+            .get(device_id)
+            .map(|device| device.actions().to_vec())
+            .ok_or_else(|| anyhow!("Device {} does not exist in adapter {}", device_id, self.id))
+    }
+
     fn get_device(&mut self, device_id: &str) -> Result<&mut Device> {
         let id = self.id.clone();
         self.devices
@@ -67,10 +123,7 @@ impl Adapter {
             .ok_or_else(|| anyhow!("Device {} does not exist in adapter {}", device_id, id))
     }
 
-    pub fn devices(&self) -> HashMap<String, DeviceDescription> {
-        self.devices
-            .iter()
-            .map(|(id, device)| (id.clone(), device.description.clone()))
-            .collect()
+    pub fn devices(&self) -> HashMap<String, Device> {
+        self.devices.clone()
     }
 }
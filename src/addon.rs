@@ -4,19 +4,57 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use rust_manifest_types::Manifest;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Scripts and stylesheets an add-on injects into the web interface.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ContentScript {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub css: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub js: Vec<String>,
+}
+
+/// The extension parts of a manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Extension {
+    #[serde(default)]
+    pub content_scripts: Vec<ContentScript>,
+    #[serde(default)]
+    pub web_accessible_resources: Vec<String>,
+}
+
+impl Extension {
+    pub fn is_empty(&self) -> bool {
+        self.content_scripts.is_empty()
+    }
+
+    /// Whether a file of the add-on may be served to the web interface. Resources ending in `*`
+    /// allow all files starting with the part before it.
+    pub fn is_accessible(&self, path: &str) -> bool {
+        self.web_accessible_resources
+            .iter()
+            .any(|resource| match resource.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == resource,
+            })
+    }
+}
+
 #[derive(Clone)]
 pub struct Addon {
     pub manifest: Manifest,
+    pub extension: Extension,
     pub path: PathBuf,
     pub enabled: bool,
 }
 
 impl Addon {
-    pub fn new(manifest: Manifest, path: PathBuf) -> Self {
+    pub fn new(manifest: Manifest, extension: Extension, path: PathBuf) -> Self {
         Self {
             manifest,
+            extension,
             path,
             enabled: false,
         }
@@ -30,3 +68,20 @@ impl Addon {
         &self.manifest.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accessible_resources() {
+        let extension = Extension {
+            content_scripts: Vec::new(),
+            web_accessible_resources: vec!["css/*".to_owned(), "js/extension.js".to_owned()],
+        };
+        assert!(extension.is_accessible("css/extension.css"));
+        assert!(extension.is_accessible("js/extension.js"));
+        assert!(!extension.is_accessible("js/other.js"));
+        assert!(!extension.is_accessible("manifest.json"));
+    }
+}
@@ -0,0 +1,411 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    addon_manager::{AddonManager, GetAddons},
+    config::CONFIG,
+    macros::call,
+    platform,
+};
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+use xactor::{message, Actor, Context, Handler, Service};
+
+/// How often the configured lists are checked for updates of installed add-ons.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn any() -> String {
+    "*".to_owned()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Language {
+    pub name: String,
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GatewayVersions {
+    #[serde(default = "any")]
+    pub min: String,
+    #[serde(default = "any")]
+    pub max: String,
+}
+
+/// A downloadable build of an add-on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Package {
+    pub version: String,
+    pub url: String,
+    pub checksum: String,
+    pub architecture: String,
+    pub language: Language,
+    pub gateway: GatewayVersions,
+}
+
+/// An add-on as described in an add-on list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListEntry {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub homepage_url: String,
+    #[serde(default)]
+    pub license_url: String,
+    #[serde(default)]
+    pub primary_type: String,
+    pub packages: Vec<Package>,
+}
+
+/// The newest package of an add-on which runs on this gateway.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AvailableAddon {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub author: String,
+    pub homepage_url: String,
+    pub license_url: String,
+    pub primary_type: String,
+    pub version: String,
+    pub url: String,
+    pub checksum: String,
+    pub installed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    pub update_available: bool,
+}
+
+/// What the gateway is able to run.
+pub struct Platform {
+    pub architecture: String,
+    pub node_version: u32,
+    pub python_versions: Vec<String>,
+    pub gateway_version: String,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        Self {
+            architecture: platform::ARCHITECTURE.to_owned(),
+            node_version: *platform::NODE_VERSION,
+            python_versions: platform::PYTHON_VERSIONS.to_owned(),
+            gateway_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+
+    fn supports_language(&self, language: &Language) -> bool {
+        let versions = &language.versions;
+        if versions.is_empty() || versions.iter().any(|version| version == "any") {
+            return true;
+        }
+        match language.name.as_str() {
+            "nodejs" => versions.contains(&self.node_version.to_string()),
+            "python" => versions.iter().any(|version| {
+                self.python_versions.iter().any(|installed| {
+                    installed == version || installed.starts_with(&format!("{}.", version))
+                })
+            }),
+            _ => true,
+        }
+    }
+
+    fn supports_gateway(&self, gateway: &GatewayVersions) -> bool {
+        let version = &self.gateway_version;
+        (gateway.min == "*" || compare_versions(version, &gateway.min) != Ordering::Less)
+            && (gateway.max == "*" || compare_versions(version, &gateway.max) != Ordering::Greater)
+    }
+
+    pub fn supports(&self, package: &Package) -> bool {
+        (package.architecture == "any" || package.architecture == self.architecture)
+            && self.supports_language(&package.language)
+            && self.supports_gateway(&package.gateway)
+    }
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(|c| c == '-' || c == '+')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Compares dotted version numbers, ignoring pre-release and build suffixes.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (parse_version(a), parse_version(b));
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    a.cmp(&b)
+}
+
+/// Fetches and concatenates the given add-on lists. Lists which can't be fetched are skipped.
+pub async fn fetch(urls: &[String]) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for url in urls {
+        let list = async {
+            reqwest::get(url)
+                .await?
+                .error_for_status()?
+                .json::<Vec<ListEntry>>()
+                .await
+        };
+        match list.await {
+            Ok(list) => {
+                debug!("Fetched {} add-ons from {}", list.len(), url);
+                entries.extend(list);
+            }
+            Err(err) => warn!("Failed to fetch add-on list {}: {}", url, err),
+        }
+    }
+    entries
+}
+
+/// Picks the newest compatible package of every listed add-on and compares it to the versions
+/// of the installed add-ons.
+pub fn select(
+    entries: Vec<ListEntry>,
+    platform: &Platform,
+    installed: &HashMap<String, String>,
+) -> Vec<AvailableAddon> {
+    let mut available: BTreeMap<String, AvailableAddon> = BTreeMap::new();
+    for entry in entries {
+        let package = match entry
+            .packages
+            .into_iter()
+            .filter(|package| platform.supports(package))
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+        {
+            Some(package) => package,
+            None => continue,
+        };
+        if let Some(known) = available.get(&entry.id) {
+            if compare_versions(&known.version, &package.version) != Ordering::Less {
+                continue;
+            }
+        }
+        let installed_version = installed.get(&entry.id).cloned();
+        let update_available = installed_version.as_ref().map_or(false, |version| {
+            compare_versions(&package.version, version) == Ordering::Greater
+        });
+        available.insert(
+            entry.id.to_owned(),
+            AvailableAddon {
+                id: entry.id,
+                name: entry.name,
+                description: entry.description,
+                author: entry.author,
+                homepage_url: entry.homepage_url,
+                license_url: entry.license_url,
+                primary_type: entry.primary_type,
+                version: package.version,
+                url: package.url,
+                checksum: package.checksum,
+                installed: installed_version.is_some(),
+                installed_version,
+                update_available,
+            },
+        );
+    }
+    available.into_values().collect()
+}
+
+async fn installed_versions() -> Result<HashMap<String, String>> {
+    Ok(call!(AddonManager.GetAddons)?
+        .into_iter()
+        .map(|(id, addon)| (id, addon.manifest.version))
+        .collect())
+}
+
+/// All add-ons of the configured lists which can be installed on this gateway.
+pub async fn available_addons() -> Result<Vec<AvailableAddon>> {
+    let entries = fetch(&CONFIG.addon_manager.list_urls).await;
+    Ok(select(
+        entries,
+        &Platform::current(),
+        &installed_versions().await?,
+    ))
+}
+
+/// Periodically checks the add-on lists for newer versions of installed add-ons.
+#[derive(Default)]
+pub struct UpdateChecker {
+    updates: BTreeMap<String, AvailableAddon>,
+}
+
+#[async_trait]
+impl Actor for UpdateChecker {
+    async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+        ctx.send_interval(CheckForUpdates, UPDATE_CHECK_INTERVAL);
+        Ok(())
+    }
+}
+
+impl Service for UpdateChecker {}
+
+#[message(result = "Result<()>")]
+#[derive(Clone)]
+pub struct CheckForUpdates;
+
+#[async_trait]
+impl Handler<CheckForUpdates> for UpdateChecker {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: CheckForUpdates) -> Result<()> {
+        self.updates = available_addons()
+            .await?
+            .into_iter()
+            .filter(|addon| addon.update_available)
+            .map(|addon| (addon.id.to_owned(), addon))
+            .collect();
+        if !self.updates.is_empty() {
+            info!("Updates available for {} add-ons", self.updates.len());
+        }
+        Ok(())
+    }
+}
+
+/// The updates found by the last check which haven't been installed since.
+#[message(result = "Result<Vec<AvailableAddon>>")]
+pub struct GetPendingUpdates;
+
+#[async_trait]
+impl Handler<GetPendingUpdates> for UpdateChecker {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetPendingUpdates,
+    ) -> Result<Vec<AvailableAddon>> {
+        let installed = match installed_versions().await {
+            Ok(installed) => installed,
+            Err(err) => {
+                error!("Failed to get installed add-ons: {}", err);
+                HashMap::new()
+            }
+        };
+        self.updates.retain(|id, update| {
+            installed.get(id).map_or(false, |version| {
+                compare_versions(&update.version, version) == Ordering::Greater
+            })
+        });
+        Ok(self.updates.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests_common::serve;
+    use serde_json::{json, Value};
+
+    fn platform() -> Platform {
+        Platform {
+            architecture: "linux-arm".to_owned(),
+            node_version: 72,
+            python_versions: vec!["3.7".to_owned()],
+            gateway_version: "1.1.0".to_owned(),
+        }
+    }
+
+    fn package(version: &str, architecture: &str, language: Value, max: &str) -> Value {
+        json!({
+            "version": version,
+            "url": format!("https://example.com/{}-{}.tgz", version, architecture),
+            "checksum": "0000",
+            "architecture": architecture,
+            "language": language,
+            "gateway": {"min": "1.0.0", "max": max}
+        })
+    }
+
+    fn fixture() -> Value {
+        json!([
+            {
+                "id": "node-addon",
+                "name": "Node Add-on",
+                "packages": [
+                    package("1.0.0", "any", json!({"name": "nodejs", "versions": ["any"]}), "*"),
+                    package("1.2.0", "linux-arm", json!({"name": "nodejs", "versions": ["72"]}), "*"),
+                    package("1.3.0", "linux-arm", json!({"name": "nodejs", "versions": ["83"]}), "*"),
+                    package("1.4.0", "darwin-x64", json!({"name": "nodejs", "versions": ["72"]}), "*")
+                ]
+            },
+            {
+                "id": "python-addon",
+                "name": "Python Add-on",
+                "packages": [
+                    package("0.5.0", "any", json!({"name": "python", "versions": ["3"]}), "*"),
+                    package("0.6.0", "any", json!({"name": "python", "versions": ["3"]}), "1.0.5")
+                ]
+            },
+            {
+                "id": "unsupported-addon",
+                "name": "Unsupported Add-on",
+                "packages": [
+                    package("2.0.0", "any", json!({"name": "python", "versions": ["2.7"]}), "*")
+                ]
+            }
+        ])
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.0.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("0.9.1", "1.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("v2.0.0-beta.1", "2.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_select() {
+        let entries: Vec<ListEntry> = serde_json::from_value(fixture()).unwrap();
+        let mut installed = HashMap::new();
+        installed.insert("node-addon".to_owned(), "1.1.0".to_owned());
+        installed.insert("python-addon".to_owned(), "0.5.0".to_owned());
+
+        let available = select(entries, &platform(), &installed);
+        assert_eq!(available.len(), 2);
+
+        let node = &available[0];
+        assert_eq!(node.id, "node-addon");
+        assert_eq!(node.version, "1.2.0");
+        assert_eq!(node.installed_version.as_deref(), Some("1.1.0"));
+        assert!(node.update_available);
+
+        let python = &available[1];
+        assert_eq!(python.id, "python-addon");
+        assert_eq!(python.version, "0.5.0");
+        assert!(python.installed);
+        assert!(!python.update_available);
+    }
+
+    #[async_test]
+    async fn test_fetch() {
+        let url = serve("/addons", fixture().to_string().into_bytes()).await;
+        let unreachable = "http://127.0.0.1:1/addons".to_owned();
+
+        let entries = fetch(&[unreachable, url]).await;
+        let ids: Vec<&str> = entries.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, vec!["node-addon", "python-addon", "unsupported-addon"]);
+
+        let available = select(entries, &platform(), &HashMap::new());
+        assert_eq!(available.len(), 2);
+        assert!(available.iter().all(|addon| !addon.installed));
+    }
+}
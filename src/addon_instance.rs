@@ -5,25 +5,170 @@
 
 use crate::{
     adapter::Adapter,
-    addon_manager::{AddonManager, AddonStarted},
-    macros::send,
+    addon_manager::{AddonDisconnected, AddonManager, AddonStarted},
+    db::{Db, GetThing},
+    device::Device,
+    macros::{call, send},
+    model::ActionRequest,
+    notifier::{Notifier, NotifierDescription, OutletDescription},
+    things_socket::{ThingAvailableMessage, ThingsMessage, ThingsMessages, ThingsSocket},
     user_config,
 };
 use anyhow::{anyhow, Result};
-use futures::{stream::SplitSink, SinkExt};
+use futures::{channel::oneshot, stream::SplitSink, SinkExt};
 use log::debug;
-use std::collections::HashMap;
+use rocket::http::Status;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{tungstenite, WebSocketStream};
+use uuid::Uuid;
 use webthings_gateway_ipc_types::{
-    Device as DeviceDescription, Message, MessageBase, PluginRegisterResponseMessageData,
-    Preferences, Units, UserProfile,
+    AdapterCancelPairingCommandMessageData, AdapterRemoveDeviceRequestMessageData,
+    AdapterStartPairingCommandMessageData, ApiHandlerApiRequestMessageData,
+    DeviceRemoveActionRequestMessageData, DeviceRequestActionRequestMessageData,
+    DeviceSetPropertyCommandMessageData, Message, MessageBase, OutletNotifyRequestMessageData,
+    PluginRegisterResponseMessageData, PluginUnloadRequestMessageData, Preferences, Units,
+    UserProfile,
 };
 use xactor::{message, Actor, Context, Handler};
 
 pub struct AddonInstance {
+    plugin_id: Option<String>,
     adapters: HashMap<String, Adapter>,
+    notifiers: HashMap<String, Notifier>,
+    api_handlers: HashSet<String>,
     stream: SplitSink<WebSocketStream<TcpStream>, tokio_tungstenite::tungstenite::Message>,
+    pending_requests: HashMap<ReplyKey, Vec<Box<dyn Resolve>>>,
+    next_message_id: i64,
+}
+
+/// Identifies the message of an add-on which completes a request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReplyKey {
+    MessageId(i64),
+    Property(String, String),
+    Action(String, String),
+    Unload,
+}
+
+impl ReplyKey {
+    fn of(message: &Message) -> Option<Self> {
+        match message {
+            Message::DevicePropertyChangedNotification(msg) => msg
+                .data
+                .property
+                .name
+                .as_ref()
+                .map(|name| ReplyKey::Property(msg.data.device_id.to_owned(), name.to_owned())),
+            Message::DeviceRequestActionResponse(msg) => Some(ReplyKey::Action(
+                msg.data.device_id.to_owned(),
+                msg.data.action_id.to_owned(),
+            )),
+            Message::DeviceRemoveActionResponse(msg) => {
+                Some(ReplyKey::MessageId(msg.data.message_id))
+            }
+            Message::OutletNotifyResponse(msg) => Some(ReplyKey::MessageId(msg.data.message_id)),
+            Message::ApiHandlerApiResponse(msg) => Some(ReplyKey::MessageId(msg.data.message_id)),
+            Message::PluginUnloadResponse(_) => Some(ReplyKey::Unload),
+            _ => None,
+        }
+    }
+}
+
+/// Completes a pending request with the reply of the add-on.
+trait Resolve: Send {
+    fn resolve(self: Box<Self>, reply: &Message);
+    /// Whether the requester stopped waiting for the reply, e.g. after a timeout.
+    fn is_canceled(&self) -> bool;
+}
+
+struct Resolver<T> {
+    sender: oneshot::Sender<Result<T>>,
+    extract: fn(&Message) -> Result<T>,
+}
+
+impl<T: Send> Resolve for Resolver<T> {
+    fn resolve(self: Box<Self>, reply: &Message) {
+        let _ = self.sender.send((self.extract)(reply));
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.sender.is_canceled()
+    }
+}
+
+/// Drops the requests nobody waits for anymore, so that replies which never arrive don't pile up.
+fn prune(pending_requests: &mut HashMap<ReplyKey, Vec<Box<dyn Resolve>>>) {
+    pending_requests.retain(|_, resolvers| {
+        resolvers.retain(|resolver| !resolver.is_canceled());
+        !resolvers.is_empty()
+    });
+}
+
+/// Why a request to an add-on did not succeed.
+#[derive(Debug)]
+pub enum RequestError {
+    Timeout(Duration),
+    /// The add-on disconnected before it replied.
+    Disconnected,
+    /// The add-on replied, but reported a failure or an unexpected message.
+    Failed(String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Timeout(timeout) => {
+                write!(f, "Add-on did not reply within {:?}", timeout)
+            }
+            RequestError::Disconnected => write!(f, "Add-on disconnected before it replied"),
+            RequestError::Failed(err) => write!(f, "Add-on request failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl RequestError {
+    /// The status with which a router answers a request that depended on the add-on.
+    pub fn status(&self) -> Status {
+        match self {
+            RequestError::Timeout(_) => Status::GatewayTimeout,
+            RequestError::Disconnected | RequestError::Failed(_) => Status::BadGateway,
+        }
+    }
+}
+
+/// A request which was sent to an add-on and awaits its reply.
+pub struct PendingRequest<T> {
+    receiver: oneshot::Receiver<Result<T>>,
+}
+
+impl<T> PendingRequest<T> {
+    pub async fn wait(self, timeout: Duration) -> Result<T, RequestError> {
+        tokio::time::timeout(timeout, self.receiver)
+            .await
+            .map_err(|_| RequestError::Timeout(timeout))?
+            .map_err(|_| RequestError::Disconnected)?
+            .map_err(|err| RequestError::Failed(err.to_string()))
+    }
+}
+
+fn unexpected<T>(message: &Message) -> Result<T> {
+    Err(anyhow!("Unexpected reply {:?}", message))
+}
+
+/// The answer of an API handler to a proxied HTTP request.
+#[derive(Debug, Clone)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub content: Option<String>,
 }
 
 impl AddonInstance {
@@ -31,8 +176,13 @@ impl AddonInstance {
         stream: SplitSink<WebSocketStream<TcpStream>, tokio_tungstenite::tungstenite::Message>,
     ) -> Self {
         Self {
+            plugin_id: None,
             adapters: HashMap::new(),
+            notifiers: HashMap::new(),
+            api_handlers: HashSet::new(),
             stream,
+            pending_requests: HashMap::new(),
+            next_message_id: 0,
         }
     }
 
@@ -41,6 +191,75 @@ impl AddonInstance {
             .get_mut(id)
             .ok_or_else(|| anyhow!("No adapter with id {} found", id))
     }
+
+    fn get_adapter_of_device(&self, device_id: &str) -> Result<&Adapter> {
+        self.adapters
+            .values()
+            .find(|adapter| adapter.has_device(device_id))
+            .ok_or_else(|| anyhow!("No adapter with device {} found", device_id))
+    }
+
+    fn get_adapter_of_device_mut(&mut self, device_id: &str) -> Result<&mut Adapter> {
+        self.adapters
+            .values_mut()
+            .find(|adapter| adapter.has_device(device_id))
+            .ok_or_else(|| anyhow!("No adapter with device {} found", device_id))
+    }
+
+    fn get_notifier_mut(&mut self, id: &str) -> Result<&mut Notifier> {
+        self.notifiers
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("No notifier with id {} found", id))
+    }
+
+    fn next_message_id(&mut self) -> i64 {
+        self.next_message_id += 1;
+        self.next_message_id
+    }
+
+    fn plugin_id(&self) -> Result<String> {
+        self.plugin_id
+            .clone()
+            .ok_or_else(|| anyhow!("Addon instance is not registered yet"))
+    }
+
+    async fn send_msg(&mut self, msg: Message) -> Result<()> {
+        debug!("Sending {:?}", &msg);
+        self.stream
+            .send(tungstenite::Message::Text(serde_json::to_string(&msg)?))
+            .await?;
+        Ok(())
+    }
+
+    /// Sends a message to the add-on and registers a request which is completed by the reply
+    /// matching `key`, converted with `extract`.
+    ///
+    /// Dropping the instance while the request is pending fails it as disconnected. Requests
+    /// which timed out are dropped the next time a request is sent.
+    async fn request<T: Send + 'static>(
+        &mut self,
+        msg: Message,
+        key: ReplyKey,
+        extract: fn(&Message) -> Result<T>,
+    ) -> Result<PendingRequest<T>> {
+        prune(&mut self.pending_requests);
+        self.send_msg(msg).await?;
+        let (sender, receiver) = oneshot::channel();
+        self.pending_requests
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push(Box::new(Resolver { sender, extract }));
+        Ok(PendingRequest { receiver })
+    }
+
+    fn resolve(&mut self, reply: &Message) {
+        let key = ReplyKey::of(reply);
+        if let Some(resolvers) = key.and_then(|key| self.pending_requests.remove(&key)) {
+            for resolver in resolvers {
+                resolver.resolve(reply);
+            }
+        }
+    }
 }
 
 impl Actor for AddonInstance {}
@@ -52,10 +271,12 @@ pub struct Msg(pub Message);
 impl Handler<Msg> for AddonInstance {
     async fn handle(&mut self, ctx: &mut Context<Self>, Msg(msg): Msg) -> Result<()> {
         debug!("Received {:?}", msg);
+        self.resolve(&msg);
 
         match msg {
             Message::PluginRegisterRequest(msg) => {
                 let id = msg.plugin_id();
+                self.plugin_id = Some(id.to_owned());
 
                 send!(AddonManager.AddonStarted(id.to_owned(), ctx.address()))?;
 
@@ -80,28 +301,39 @@ impl Handler<Msg> for AddonInstance {
                 }
                 .into();
 
-                debug!("Sending {:?}", &response);
-                self.stream
-                    .send(tungstenite::Message::Text(serde_json::to_string(
-                        &response,
-                    )?))
-                    .await?;
+                self.send_msg(response).await?;
             }
             Message::AdapterAddedNotification(msg) => {
                 let adapter = Adapter::new(msg.data.adapter_id.clone());
                 self.adapters.insert(msg.data.adapter_id, adapter);
             }
             Message::DeviceAddedNotification(msg) => {
+                let device = msg.data.device.clone();
+                let id = device.id.clone();
                 let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
-                let id = msg.data.device.id.clone();
                 adapter.add_device(msg.data.device);
-                adapter.set_connect_state(id, true).await?;
+                adapter.set_connect_state(id.clone(), true).await?;
+                match call!(Db.GetThing(id.clone())) {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        send!(
+                            ThingsSocket.ThingsMessage(ThingsMessages::ThingAvailableMessage(
+                                ThingAvailableMessage::new(device)
+                            ))
+                        )?;
+                    }
+                    Err(err) => error!("Failed to look up thing {}: {}", id, err),
+                }
+            }
+            Message::AdapterRemoveDeviceResponse(msg) => {
+                let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
+                adapter.remove_device(&msg.data.device_id);
             }
             Message::DevicePropertyChangedNotification(msg) => {
+                let device_id = msg.data.device_id;
+                let property = msg.data.property;
                 let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
-                adapter
-                    .update_property(msg.data.device_id, msg.data.property)
-                    .await?;
+                adapter.update_property(device_id, property).await?;
             }
             Message::DeviceConnectedStateNotification(msg) => {
                 let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
@@ -109,6 +341,43 @@ impl Handler<Msg> for AddonInstance {
                     .set_connect_state(msg.data.device_id, msg.data.connected)
                     .await?;
             }
+            Message::DeviceActionStatusNotification(msg) => {
+                let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
+                adapter
+                    .update_action(&msg.data.device_id, msg.data.action)
+                    .await?;
+            }
+            Message::DeviceRemoveActionResponse(msg) if msg.data.success => {
+                let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
+                adapter.remove_action(&msg.data.device_id, &msg.data.action_id)?;
+            }
+            Message::DeviceEventNotification(msg) => {
+                let adapter = self.get_adapter_mut(&msg.data.adapter_id)?;
+                adapter
+                    .notify_event(&msg.data.device_id, msg.data.event)
+                    .await?;
+            }
+            Message::NotifierAddedNotification(msg) => {
+                info!("Notifier {} added", msg.data.notifier_id);
+                let notifier = Notifier::new(msg.data.notifier_id.clone(), msg.data.name);
+                self.notifiers.insert(msg.data.notifier_id, notifier);
+            }
+            Message::OutletAddedNotification(msg) => {
+                let outlet = OutletDescription {
+                    id: msg.data.outlet.id,
+                    name: msg.data.outlet.name,
+                };
+                self.get_notifier_mut(&msg.data.notifier_id)?
+                    .add_outlet(outlet);
+            }
+            Message::OutletRemovedNotification(msg) => {
+                self.get_notifier_mut(&msg.data.notifier_id)?
+                    .remove_outlet(&msg.data.outlet_id);
+            }
+            Message::ApiHandlerAddedNotification(msg) => {
+                info!("API handler {} added", msg.data.package_name);
+                self.api_handlers.insert(msg.data.package_name);
+            }
             _ => {}
         };
 
@@ -116,7 +385,7 @@ impl Handler<Msg> for AddonInstance {
     }
 }
 
-#[message(result = "Result<HashMap<String, DeviceDescription>>")]
+#[message(result = "Result<HashMap<String, Device>>")]
 pub struct GetDevices;
 
 #[async_trait]
@@ -125,7 +394,7 @@ impl Handler<GetDevices> for AddonInstance {
         &mut self,
         _ctx: &mut Context<Self>,
         _msg: GetDevices,
-    ) -> Result<HashMap<String, DeviceDescription>> {
+    ) -> Result<HashMap<String, Device>> {
         let mut devices = HashMap::new();
         for adapter in self.adapters.values() {
             devices.extend(adapter.devices());
@@ -133,3 +402,535 @@ impl Handler<GetDevices> for AddonInstance {
         Ok(devices)
     }
 }
+
+#[message(result = "Result<bool>")]
+pub struct HasDevice(pub String);
+
+#[async_trait]
+impl Handler<HasDevice> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        HasDevice(device_id): HasDevice,
+    ) -> Result<bool> {
+        Ok(self
+            .adapters
+            .values()
+            .any(|adapter| adapter.has_device(&device_id)))
+    }
+}
+
+/// Sends a `DeviceSetPropertyCommand` to the add-on.
+///
+/// The request completes with the new value once the add-on reports the change with a
+/// `DevicePropertyChangedNotification`.
+#[message(result = "Result<PendingRequest<Value>>")]
+pub struct SetPropertyValue(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<SetPropertyValue> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        SetPropertyValue(device_id, property_name, value): SetPropertyValue,
+    ) -> Result<PendingRequest<Value>> {
+        let plugin_id = self.plugin_id()?;
+        let adapter_id = self.get_adapter_of_device(&device_id)?.id().to_owned();
+
+        let message: Message = DeviceSetPropertyCommandMessageData {
+            plugin_id,
+            adapter_id,
+            device_id: device_id.clone(),
+            property_name: property_name.clone(),
+            property_value: value,
+        }
+        .into();
+        self.request(
+            message,
+            ReplyKey::Property(device_id, property_name),
+            |reply| match reply {
+                Message::DevicePropertyChangedNotification(msg) => {
+                    Ok(msg.data.property.value.clone().unwrap_or(Value::Null))
+                }
+                reply => unexpected(reply),
+            },
+        )
+        .await
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct RemoveDevice(pub String);
+
+#[async_trait]
+impl Handler<RemoveDevice> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RemoveDevice(device_id): RemoveDevice,
+    ) -> Result<()> {
+        let plugin_id = self.plugin_id()?;
+        let adapter_id = self.get_adapter_of_device(&device_id)?.id().to_owned();
+
+        let message: Message = AdapterRemoveDeviceRequestMessageData {
+            plugin_id,
+            adapter_id,
+            device_id,
+        }
+        .into();
+        self.send_msg(message).await
+    }
+}
+
+/// Sends a `DeviceRequestActionRequest` to the add-on and returns the new action request.
+///
+/// The pending request completes once the add-on confirms the action with a
+/// `DeviceRequestActionResponse`.
+#[message(result = "Result<(ActionRequest, PendingRequest<()>)>")]
+pub struct RequestAction(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<RequestAction> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RequestAction(device_id, action_name, input): RequestAction,
+    ) -> Result<(ActionRequest, PendingRequest<()>)> {
+        let plugin_id = self.plugin_id()?;
+        let adapter_id = self.get_adapter_of_device(&device_id)?.id().to_owned();
+        let action = ActionRequest::new(
+            &device_id,
+            Uuid::new_v4().to_string(),
+            action_name.clone(),
+            input.clone(),
+        );
+
+        let message: Message = DeviceRequestActionRequestMessageData {
+            plugin_id,
+            adapter_id,
+            device_id: device_id.clone(),
+            action_name,
+            action_id: action.id.clone(),
+            input,
+        }
+        .into();
+        let request = self
+            .request(
+                message,
+                ReplyKey::Action(device_id.clone(), action.id.clone()),
+                |reply| match reply {
+                    Message::DeviceRequestActionResponse(msg) if msg.data.success => Ok(()),
+                    Message::DeviceRequestActionResponse(msg) => Err(anyhow!(
+                        "Add-on failed to perform action {}",
+                        msg.data.action_id
+                    )),
+                    reply => unexpected(reply),
+                },
+            )
+            .await?;
+
+        self.get_adapter_of_device_mut(&device_id)?
+            .add_action(&device_id, action.clone())
+            .await?;
+        Ok((action, request))
+    }
+}
+
+/// Sends a `DeviceRemoveActionRequest` to the add-on, which is completed by the matching
+/// `DeviceRemoveActionResponse`. The action request is only dropped once the add-on confirms
+/// its removal.
+#[message(result = "Result<PendingRequest<()>>")]
+pub struct RemoveAction(pub String, pub String, pub String);
+
+#[async_trait]
+impl Handler<RemoveAction> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RemoveAction(device_id, action_name, action_id): RemoveAction,
+    ) -> Result<PendingRequest<()>> {
+        let plugin_id = self.plugin_id()?;
+        let message_id = self.next_message_id();
+        let adapter = self.get_adapter_of_device(&device_id)?;
+        let adapter_id = adapter.id().to_owned();
+        let action = adapter
+            .actions(&device_id)?
+            .into_iter()
+            .find(|action| action.id == action_id)
+            .ok_or_else(|| anyhow!("Device {} has no action request {}", device_id, action_id))?;
+        if action.name != action_name {
+            return Err(anyhow!(
+                "Action request {} is not a {} action",
+                action_id,
+                action_name
+            ));
+        }
+
+        let message: Message = DeviceRemoveActionRequestMessageData {
+            plugin_id,
+            adapter_id,
+            device_id,
+            action_name,
+            action_id,
+            message_id,
+        }
+        .into();
+        self.request(
+            message,
+            ReplyKey::MessageId(message_id),
+            |reply| match reply {
+                Message::DeviceRemoveActionResponse(msg) if msg.data.success => Ok(()),
+                Message::DeviceRemoveActionResponse(msg) => Err(anyhow!(
+                    "Add-on failed to remove action {}",
+                    msg.data.action_id
+                )),
+                reply => unexpected(reply),
+            },
+        )
+        .await
+    }
+}
+
+/// Marks an action request as failed after its add-on did not confirm it.
+#[message(result = "Result<()>")]
+pub struct FailAction(pub String, pub String);
+
+#[async_trait]
+impl Handler<FailAction> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        FailAction(device_id, action_id): FailAction,
+    ) -> Result<()> {
+        self.get_adapter_of_device_mut(&device_id)?
+            .fail_action(&device_id, &action_id)
+            .await
+    }
+}
+
+#[message(result = "Result<Vec<ActionRequest>>")]
+pub struct GetActions(pub String);
+
+#[async_trait]
+impl Handler<GetActions> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetActions(device_id): GetActions,
+    ) -> Result<Vec<ActionRequest>> {
+        self.get_adapter_of_device(&device_id)?.actions(&device_id)
+    }
+}
+
+/// Puts every adapter of the add-on into pairing mode for the given duration.
+#[message(result = "Result<()>")]
+pub struct StartPairing(pub Duration);
+
+#[async_trait]
+impl Handler<StartPairing> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        StartPairing(timeout): StartPairing,
+    ) -> Result<()> {
+        let plugin_id = self.plugin_id()?;
+        let adapter_ids: Vec<String> = self.adapters.keys().cloned().collect();
+        for adapter_id in adapter_ids {
+            let message: Message = AdapterStartPairingCommandMessageData {
+                plugin_id: plugin_id.clone(),
+                adapter_id,
+                timeout: timeout.as_secs_f64(),
+            }
+            .into();
+            self.send_msg(message).await?;
+        }
+        Ok(())
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct CancelPairing;
+
+#[async_trait]
+impl Handler<CancelPairing> for AddonInstance {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: CancelPairing) -> Result<()> {
+        let plugin_id = self.plugin_id()?;
+        let adapter_ids: Vec<String> = self.adapters.keys().cloned().collect();
+        for adapter_id in adapter_ids {
+            let message: Message = AdapterCancelPairingCommandMessageData {
+                plugin_id: plugin_id.clone(),
+                adapter_id,
+            }
+            .into();
+            self.send_msg(message).await?;
+        }
+        Ok(())
+    }
+}
+
+#[message(result = "Result<Vec<NotifierDescription>>")]
+pub struct GetNotifiers;
+
+#[async_trait]
+impl Handler<GetNotifiers> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetNotifiers,
+    ) -> Result<Vec<NotifierDescription>> {
+        Ok(self
+            .notifiers
+            .values()
+            .map(|notifier| notifier.description())
+            .collect())
+    }
+}
+
+/// Returns the outlets of a notifier, or `None` if the add-on has no such notifier.
+#[message(result = "Result<Option<Vec<OutletDescription>>>")]
+pub struct GetOutlets(pub String);
+
+#[async_trait]
+impl Handler<GetOutlets> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetOutlets(notifier_id): GetOutlets,
+    ) -> Result<Option<Vec<OutletDescription>>> {
+        Ok(self
+            .notifiers
+            .get(&notifier_id)
+            .map(|notifier| notifier.outlets()))
+    }
+}
+
+/// Sends an `OutletNotifyRequest` to the add-on.
+///
+/// The request completes once the add-on answers with an `OutletNotifyResponse`.
+#[message(result = "Result<PendingRequest<()>>")]
+pub struct Notify {
+    pub notifier_id: String,
+    pub outlet_id: String,
+    pub title: String,
+    pub message: String,
+    pub level: i64,
+}
+
+#[async_trait]
+impl Handler<Notify> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: Notify,
+    ) -> Result<PendingRequest<()>> {
+        let plugin_id = self.plugin_id()?;
+        if !self
+            .get_notifier_mut(&msg.notifier_id)?
+            .has_outlet(&msg.outlet_id)
+        {
+            return Err(anyhow!(
+                "Notifier {} has no outlet {}",
+                msg.notifier_id,
+                msg.outlet_id
+            ));
+        }
+        let message_id = self.next_message_id();
+
+        let message: Message = OutletNotifyRequestMessageData {
+            plugin_id,
+            notifier_id: msg.notifier_id,
+            outlet_id: msg.outlet_id,
+            title: msg.title,
+            message: msg.message,
+            level: msg.level,
+            message_id,
+        }
+        .into();
+        self.request(
+            message,
+            ReplyKey::MessageId(message_id),
+            |reply| match reply {
+                Message::OutletNotifyResponse(_) => Ok(()),
+                reply => unexpected(reply),
+            },
+        )
+        .await
+    }
+}
+
+#[message(result = "Result<bool>")]
+pub struct HasApiHandler(pub String);
+
+#[async_trait]
+impl Handler<HasApiHandler> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        HasApiHandler(package_name): HasApiHandler,
+    ) -> Result<bool> {
+        Ok(self.api_handlers.contains(&package_name))
+    }
+}
+
+/// Sends an `ApiHandlerApiRequest` to the add-on.
+///
+/// The request completes with the `ApiHandlerApiResponse` of the add-on.
+#[message(result = "Result<PendingRequest<ApiResponse>>")]
+pub struct ApiRequest {
+    pub package_name: String,
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, Value>,
+    pub body: BTreeMap<String, Value>,
+}
+
+#[async_trait]
+impl Handler<ApiRequest> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ApiRequest,
+    ) -> Result<PendingRequest<ApiResponse>> {
+        let plugin_id = self.plugin_id()?;
+        if !self.api_handlers.contains(&msg.package_name) {
+            return Err(anyhow!("No API handler {} found", msg.package_name));
+        }
+        let message_id = self.next_message_id();
+
+        let message: Message = ApiHandlerApiRequestMessageData {
+            plugin_id,
+            package_name: msg.package_name,
+            message_id,
+            method: msg.method,
+            path: msg.path,
+            query: msg.query.into_iter().collect(),
+            body: msg.body.into_iter().collect(),
+        }
+        .into();
+        self.request(
+            message,
+            ReplyKey::MessageId(message_id),
+            |reply| match reply {
+                Message::ApiHandlerApiResponse(msg) => Ok(ApiResponse {
+                    status: msg.data.response.status as u16,
+                    content_type: msg.data.response.content_type.clone(),
+                    content: msg.data.response.content.clone(),
+                }),
+                reply => unexpected(reply),
+            },
+        )
+        .await
+    }
+}
+
+/// Asks the add-on to shut down, which is completed by its `PluginUnloadResponse`.
+#[message(result = "Result<PendingRequest<()>>")]
+pub struct Unload;
+
+#[async_trait]
+impl Handler<Unload> for AddonInstance {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: Unload,
+    ) -> Result<PendingRequest<()>> {
+        let plugin_id = self.plugin_id()?;
+        let message: Message = PluginUnloadRequestMessageData { plugin_id }.into();
+        self.request(message, ReplyKey::Unload, |reply| match reply {
+            Message::PluginUnloadResponse(_) => Ok(()),
+            reply => unexpected(reply),
+        })
+        .await
+    }
+}
+
+/// Cleans up after the add-on closed its connection: all of its devices are marked as
+/// disconnected, pending requests fail and the instance stops.
+#[message(result = "Result<()>")]
+pub struct Disconnect;
+
+#[async_trait]
+impl Handler<Disconnect> for AddonInstance {
+    async fn handle(&mut self, ctx: &mut Context<Self>, _msg: Disconnect) -> Result<()> {
+        for adapter in self.adapters.values_mut() {
+            for device_id in adapter.devices().into_keys() {
+                if let Err(err) = adapter.set_connect_state(device_id.to_owned(), false).await {
+                    error!("Failed to disconnect device {}: {}", device_id, err);
+                }
+            }
+        }
+        self.pending_requests.clear();
+        if let Some(plugin_id) = self.plugin_id.take() {
+            send!(AddonManager.AddonDisconnected(plugin_id, ctx.actor_id()))?;
+        }
+        ctx.stop(None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending<T>() -> (oneshot::Sender<Result<T>>, PendingRequest<T>) {
+        let (sender, receiver) = oneshot::channel();
+        (sender, PendingRequest { receiver })
+    }
+
+    #[async_test]
+    async fn test_pending_request() {
+        let timeout = Duration::from_millis(10);
+
+        let (sender, request) = pending();
+        sender.send(Ok(42)).unwrap();
+        assert_eq!(request.wait(timeout).await.unwrap(), 42);
+
+        let (sender, request) = pending::<i64>();
+        sender.send(Err(anyhow!("Unsupported"))).unwrap();
+        assert!(matches!(
+            request.wait(timeout).await,
+            Err(RequestError::Failed(_))
+        ));
+
+        let (sender, request) = pending::<i64>();
+        drop(sender);
+        assert!(matches!(
+            request.wait(timeout).await,
+            Err(RequestError::Disconnected)
+        ));
+
+        let (_sender, request) = pending::<i64>();
+        assert!(matches!(
+            request.wait(timeout).await,
+            Err(RequestError::Timeout(_))
+        ));
+    }
+
+    #[async_test]
+    async fn test_prune_timed_out_requests() {
+        let mut pending_requests: HashMap<ReplyKey, Vec<Box<dyn Resolve>>> = HashMap::new();
+        let (sender, timed_out) = pending::<()>();
+        pending_requests
+            .entry(ReplyKey::Unload)
+            .or_default()
+            .push(Box::new(Resolver {
+                sender,
+                extract: |_| Ok(()),
+            }));
+        let (sender, _waiting) = pending::<()>();
+        pending_requests
+            .entry(ReplyKey::MessageId(1))
+            .or_default()
+            .push(Box::new(Resolver {
+                sender,
+                extract: |_| Ok(()),
+            }));
+
+        assert!(timed_out.wait(Duration::from_millis(10)).await.is_err());
+        prune(&mut pending_requests);
+        assert_eq!(
+            pending_requests.keys().collect::<Vec<_>>(),
+            vec![&ReplyKey::MessageId(1)]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    addon_manager::{AddonManager, InstallAddonFromFile},
+    macros::{call, send},
+    things_socket::{AddonJobMessage, ThingsMessage, ThingsMessages, ThingsSocket},
+};
+use anyhow::{anyhow, bail, Context as AnyhowContext, Result};
+use log::{error, info};
+use openssl::sha::Sha256;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tempdir::TempDir;
+use tokio::{fs::File, io::AsyncWriteExt};
+use xactor::{message, Actor, Addr, Context, Handler, Service};
+
+/// Largest add-on package which is downloaded.
+const MAX_DOWNLOAD_SIZE: u64 = 200 * 1024 * 1024;
+/// How long a whole download may take.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Minimum time between two progress reports of a download.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// How many finished jobs are kept for clients to look up.
+const MAX_FINISHED_JOBS: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Downloading,
+    Installing,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// The installation of an add-on from a URL, which runs in the background.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: u64,
+    pub addon_id: String,
+    pub status: JobStatus,
+    pub downloaded: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Streams a file to disk while hashing it and fails if it exceeds `max_size` or its SHA-256
+/// checksum doesn't match. `progress` is called with the downloaded and expected number of
+/// bytes.
+pub async fn download(
+    url: &str,
+    dest: &Path,
+    checksum: &str,
+    max_size: u64,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let total = response.content_length();
+    if total.map_or(false, |total| total > max_size) {
+        bail!("Download exceeds {} bytes", max_size);
+    }
+
+    let mut file = File::create(dest)
+        .await
+        .context(format!("Failed to create {:?}", dest))?;
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0;
+    let mut last_report = Instant::now();
+    progress(downloaded, total);
+    while let Some(chunk) = response.chunk().await? {
+        downloaded += chunk.len() as u64;
+        if downloaded > max_size {
+            bail!("Download exceeds {} bytes", max_size);
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            progress(downloaded, total);
+        }
+    }
+    file.flush().await?;
+    progress(downloaded, total);
+
+    if hex::encode(hasher.finish()) != checksum.to_lowercase() {
+        bail!("Checksum of {} did not match", url);
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct AddonJobs {
+    jobs: BTreeMap<u64, Job>,
+    next_id: u64,
+}
+
+impl Actor for AddonJobs {}
+
+impl Service for AddonJobs {}
+
+#[message(result = "Result<Job>")]
+struct CreateJob(String);
+
+#[async_trait]
+impl Handler<CreateJob> for AddonJobs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateJob(addon_id): CreateJob,
+    ) -> Result<Job> {
+        self.next_id += 1;
+        let job = Job {
+            id: self.next_id,
+            addon_id,
+            status: JobStatus::Downloading,
+            downloaded: 0,
+            total: None,
+            error: None,
+        };
+        self.jobs.insert(job.id, job.clone());
+        Ok(job)
+    }
+}
+
+#[message(result = "()")]
+struct UpdateJob(Job);
+
+#[async_trait]
+impl Handler<UpdateJob> for AddonJobs {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, UpdateJob(job): UpdateJob) {
+        self.jobs.insert(job.id, job.clone());
+        let finished: Vec<u64> = self
+            .jobs
+            .values()
+            .filter(|job| job.status.finished())
+            .map(|job| job.id)
+            .collect();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+        {
+            self.jobs.remove(id);
+        }
+        if let Err(err) =
+            send!(ThingsSocket
+                .ThingsMessage(ThingsMessages::AddonJobMessage(AddonJobMessage::new(job))))
+        {
+            error!("Failed to report add-on job: {}", err);
+        }
+    }
+}
+
+#[message(result = "Result<Job>")]
+pub struct GetJob(pub u64);
+
+#[async_trait]
+impl Handler<GetJob> for AddonJobs {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, GetJob(id): GetJob) -> Result<Job> {
+        self.jobs
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown job {}", id))
+    }
+}
+
+fn report(jobs: &Addr<AddonJobs>, job: &Job) {
+    if let Err(err) = jobs.send(UpdateJob(job.clone())) {
+        error!("Failed to update add-on job {}: {}", job.id, err);
+    }
+}
+
+async fn run(
+    jobs: &Addr<AddonJobs>,
+    job: &mut Job,
+    url: String,
+    checksum: String,
+    enable: bool,
+) -> Result<()> {
+    let temp_dir = TempDir::new(&job.addon_id)?;
+    let dest_path = temp_dir.path().join(format!("{}.tar.gz", job.addon_id));
+    info!("Fetching add-on {} as {:?}", url, dest_path);
+    download(
+        &url,
+        &dest_path,
+        &checksum,
+        MAX_DOWNLOAD_SIZE,
+        |downloaded, total| {
+            job.downloaded = downloaded;
+            job.total = total;
+            report(jobs, job);
+        },
+    )
+    .await?;
+
+    job.status = JobStatus::Installing;
+    report(jobs, job);
+    call!(AddonManager.InstallAddonFromFile(dest_path, Some(job.addon_id.to_owned()), enable))?;
+    Ok(())
+}
+
+/// Starts downloading and installing an add-on in the background and returns the job tracking
+/// its progress.
+pub async fn install_from_url(
+    addon_id: String,
+    url: String,
+    checksum: String,
+    enable: bool,
+) -> Result<Job> {
+    reqwest::Url::parse(&url).context(format!("Invalid url {}", url))?;
+    let jobs = AddonJobs::from_registry().await?;
+    let job = jobs
+        .call(CreateJob(addon_id))
+        .await
+        .map_err(|err| anyhow!(err))
+        .flatten()?;
+    let mut running = job.clone();
+    tokio::spawn(async move {
+        match run(&jobs, &mut running, url, checksum, enable).await {
+            Ok(()) => {
+                info!("Installed add-on {}", running.addon_id);
+                running.status = JobStatus::Completed;
+            }
+            Err(err) => {
+                error!("Failed to install add-on {}: {:?}", running.addon_id, err);
+                running.status = JobStatus::Failed;
+                running.error = Some(format!("{:#}", err));
+            }
+        }
+        report(&jobs, &running);
+    });
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests_common::serve;
+
+    fn checksum(body: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(body);
+        hex::encode(hasher.finish())
+    }
+
+    #[async_test]
+    async fn test_download() {
+        let dir = TempDir::new("download").unwrap();
+        let dest = dir.path().join("package.tar.gz");
+        let body = vec![42; 100_000];
+        let url = serve("/package.tar.gz", body.clone()).await;
+
+        let mut reports = Vec::new();
+        download(
+            &url,
+            &dest,
+            &checksum(&body),
+            1_000_000,
+            |downloaded, total| reports.push((downloaded, total)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(reports.first(), Some(&(0, Some(100_000))));
+        assert_eq!(reports.last(), Some(&(100_000, Some(100_000))));
+    }
+
+    #[async_test]
+    async fn test_download_checksum_mismatch() {
+        let dir = TempDir::new("download").unwrap();
+        let dest = dir.path().join("package.tar.gz");
+        let url = serve("/package.tar.gz", vec![42; 1000]).await;
+
+        let result = download(&url, &dest, &checksum(b"other"), 1_000_000, |_, _| {}).await;
+        assert!(result.is_err());
+    }
+
+    #[async_test]
+    async fn test_download_size_limit() {
+        let dir = TempDir::new("download").unwrap();
+        let dest = dir.path().join("package.tar.gz");
+        let body = vec![42; 1000];
+        let url = serve("/package.tar.gz", body.clone()).await;
+
+        let result = download(&url, &dest, &checksum(&body), 999, |_, _| {}).await;
+        assert!(result.is_err());
+    }
+}
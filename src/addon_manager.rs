@@ -4,46 +4,125 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    addon::Addon,
-    addon_instance::{self, AddonInstance},
+    addon::{Addon, Extension},
+    addon_instance::{self, AddonInstance, ApiResponse, PendingRequest, RequestError},
+    config::CONFIG,
     db::{Db, GetSetting, SetSetting, SetSettingIfNotExists},
+    device::Device,
     macros::call,
+    model::ActionRequest,
+    notifier::{NotifierDescription, OutletDescription},
+    package,
     process_manager::{ProcessManager, StartAddon, StopAddon},
     user_config,
 };
 use anyhow::{anyhow, bail, Context as AnyhowContext, Result};
-use flate2::read::GzDecoder;
-use fs_extra::{dir::CopyOptions, move_items};
 use log::{error, info};
 use rust_manifest_types::Manifest;
-use serde_json::json;
-use sha256::digest_bytes;
+use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
-    fs::{self, File},
-    io::Write,
+    collections::{BTreeMap, HashMap},
+    fs,
     marker::PhantomData,
     path::PathBuf,
+    time::Duration,
 };
-use tar::Archive;
 use tempdir::TempDir;
-use webthings_gateway_ipc_types::Device as DeviceDescription;
 use xactor::{message, Actor, Addr, Context, Handler, Service};
 
+/// How long an add-on may take to shut down before its process is stopped.
+const UNLOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Default)]
 pub struct AddonManager {
     installed_addons: HashMap<String, Addon>,
     running_addons: HashMap<String, Addr<AddonInstance>>,
+    pairing_session: Option<u64>,
+    next_pairing_session: u64,
+    pending_installs: HashMap<String, u64>,
+    next_install: u64,
 }
 
 impl AddonManager {
+    async fn get_instance_of_device(&self, device_id: &str) -> Result<Addr<AddonInstance>> {
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::HasDevice(device_id.to_owned()))
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(true) => return Ok(instance.clone()),
+                Ok(false) => {}
+                Err(err) => error!("Failed to query devices of add-on {}: {}", addon_id, err),
+            }
+        }
+        Err(anyhow!("No running add-on owns device {}", device_id))
+    }
+
+    async fn get_instance_of_notifier(&self, notifier_id: &str) -> Result<Addr<AddonInstance>> {
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::GetOutlets(notifier_id.to_owned()))
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(Some(_)) => return Ok(instance.clone()),
+                Ok(None) => {}
+                Err(err) => error!("Failed to query notifiers of add-on {}: {}", addon_id, err),
+            }
+        }
+        Err(anyhow!("No running add-on owns notifier {}", notifier_id))
+    }
+
+    async fn get_instance_of_api_handler(&self, package_name: &str) -> Result<Addr<AddonInstance>> {
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::HasApiHandler(package_name.to_owned()))
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(true) => return Ok(instance.clone()),
+                Ok(false) => {}
+                Err(err) => error!(
+                    "Failed to query API handler of add-on {}: {}",
+                    addon_id, err
+                ),
+            }
+        }
+        Err(anyhow!(
+            "No running add-on provides API handler {}",
+            package_name
+        ))
+    }
+
+    async fn cancel_pairing(&mut self) {
+        if let Some(session) = self.pairing_session.take() {
+            info!("Cancelling pairing session {}", session);
+            for (addon_id, instance) in &self.running_addons {
+                if let Err(err) = instance
+                    .call(addon_instance::CancelPairing)
+                    .await
+                    .map_err(|err| anyhow!(err))
+                    .flatten()
+                {
+                    error!("Failed to cancel pairing of add-on {}: {}", addon_id, err);
+                }
+            }
+        }
+    }
+
     async fn load_addon(&mut self, path: PathBuf) -> Result<()> {
-        let file = fs::File::open(path.join("manifest.json"))
+        let content = fs::read_to_string(path.join("manifest.json"))
             .context(anyhow!("Could not open manifest.json file in {:?}", path,))?;
         let manifest: Manifest =
-            serde_json::from_reader(file).context(anyhow!("Could not read manifest.json"))?;
+            serde_json::from_str(&content).context(anyhow!("Could not read manifest.json"))?;
+        let extension: Extension = serde_json::from_str(&content)
+            .context(anyhow!("Could not read extensions from manifest.json"))?;
 
-        let mut addon = Addon::new(manifest, path);
+        let mut addon = Addon::new(manifest, extension, path);
         let addon_id = addon.id().to_owned();
         let path = addon.path.to_owned();
         let exec = addon.exec().to_owned();
@@ -64,6 +143,23 @@ impl AddonManager {
     }
 
     async fn unload_addon(&mut self, id: String) -> Result<()> {
+        if let Some(instance) = self.running_addons.get(&id) {
+            let unloaded = match instance
+                .call(addon_instance::Unload)
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(request) => request
+                    .wait(UNLOAD_TIMEOUT)
+                    .await
+                    .map_err(|err| anyhow!(err)),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = unloaded {
+                warn!("Add-on {} did not unload cleanly: {}", id, err);
+            }
+        }
         call!(ProcessManager.StopAddon(id.clone()))
     }
 
@@ -74,12 +170,19 @@ impl AddonManager {
             .ok_or_else(|| anyhow!("Package {} not installed", id))?;
         Ok(addon.enabled)
     }
+    /// Installs a packaged add-on and returns the ID from its manifest. If the caller expects
+    /// a specific add-on, packages of any other add-on are rejected.
+    ///
+    /// The package is extracted and validated in a staging directory before it replaces the
+    /// installed version, which is kept as a backup. If the new version fails to load, or to
+    /// register within the configured registration timeout, the backup is restored.
     async fn install_addon(
         &mut self,
-        package_id: String,
+        ctx: &mut Context<Self>,
+        expected_id: Option<String>,
         package_path: PathBuf,
         enable: bool,
-    ) -> Result<()> {
+    ) -> Result<String> {
         if !package_path.is_file() {
             return Err(anyhow!(format!(
                 "Cannot extract invalid path: {:?}",
@@ -89,35 +192,113 @@ impl AddonManager {
 
         info!("Expanding add-on {:?}", package_path);
 
-        let package_dir = package_path
-            .parent()
-            .ok_or_else(|| anyhow!("Missing parent directory"))?;
+        let staging_dir = TempDir::new_in(user_config::ADDONS_STAGING_DIR.as_path(), "install")?;
+        let staged_path = package::extract(
+            &package_path,
+            staging_dir.path(),
+            &package::Limits::default(),
+        )
+        .context("Failed to extract package")?;
+        let package_id = package::validate(&staged_path, expected_id.as_deref())?;
 
-        let file = File::open(package_path.to_owned()).map_err(|err| anyhow!(err))?;
-        Archive::new(GzDecoder::new(file))
-            .unpack(package_dir)
-            .context("Failed to extract package")?;
-
-        self.uninstall_addon(package_id.to_owned(), false).await?;
+        if let Err(err) = self.unload_addon(package_id.to_owned()).await {
+            error!("Failed to unload {} properly: {:?}", package_id, err);
+        }
+        self.installed_addons.remove(&package_id);
 
         let addon_path = user_config::ADDONS_DIR.join(package_id.to_owned());
-        let entries: Vec<PathBuf> = package_dir
-            .join("package")
-            .read_dir()?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path())
-            .collect();
-        fs::create_dir(addon_path.to_owned())?;
-        move_items(&entries, addon_path.to_owned(), &CopyOptions::new())
-            .context("Failed to move package")?;
+        let backup_path = user_config::ADDONS_BACKUP_DIR.join(package_id.to_owned());
+        let has_backup = addon_path.exists();
+        if has_backup {
+            if backup_path.exists() {
+                fs::remove_dir_all(&backup_path)
+                    .context(format!("Error removing old backup of {}", package_id))?;
+            }
+            fs::rename(&addon_path, &backup_path)
+                .context(format!("Failed to back up {}", package_id))?;
+        }
+        if let Err(err) = fs::rename(&staged_path, &addon_path) {
+            if has_backup {
+                fs::rename(&backup_path, &addon_path)
+                    .context(format!("Failed to restore {}", package_id))?;
+            }
+            return Err(anyhow!(err).context(format!("Failed to move {} into place", package_id)));
+        }
 
         let enabled_key = format!("addons.{}.enabled", package_id);
         if enable {
             call!(Db.SetSetting(enabled_key, true))?;
         }
 
-        self.load_addon(addon_path).await?;
+        if let Err(err) = self.load_addon(addon_path.to_owned()).await {
+            error!("Failed to load new version of {}: {:?}", package_id, err);
+            if has_backup {
+                self.rollback_addon(package_id.to_owned()).await?;
+            } else {
+                self.uninstall_addon(package_id.to_owned(), false).await?;
+            }
+            return Err(err.context(format!("Failed to load {}", package_id)));
+        }
 
+        if self.addon_enabled(package_id.to_owned()).await? {
+            self.next_install += 1;
+            let install = self.next_install;
+            self.pending_installs.insert(package_id.to_owned(), install);
+            ctx.send_later(
+                RegistrationTimeout(package_id.to_owned(), install),
+                Duration::from_secs(CONFIG.addon_manager.registration_timeout),
+            );
+        }
+
+        Ok(package_id)
+    }
+
+    /// Replaces the installed version of an add-on with the backup kept by the last install.
+    /// The replaced version is discarded.
+    async fn rollback_addon(&mut self, package_id: String) -> Result<()> {
+        let backup_path = user_config::ADDONS_BACKUP_DIR.join(package_id.to_owned());
+        if !backup_path.is_dir() {
+            bail!("No previous version of {} available", package_id);
+        }
+        info!("Rolling back add-on {}", package_id);
+
+        self.pending_installs.remove(&package_id);
+        if let Err(err) = self.unload_addon(package_id.to_owned()).await {
+            error!("Failed to unload {} properly: {:?}", package_id, err);
+        }
+        self.installed_addons.remove(&package_id);
+
+        let addon_path = user_config::ADDONS_DIR.join(package_id.to_owned());
+        if addon_path.exists() {
+            let discarded_dir =
+                TempDir::new_in(user_config::ADDONS_STAGING_DIR.as_path(), "rollback")?;
+            fs::rename(&addon_path, discarded_dir.path().join("package")).context(format!(
+                "Failed to remove current version of {}",
+                package_id
+            ))?;
+        }
+        fs::rename(&backup_path, &addon_path)
+            .context(format!("Failed to restore {}", package_id))?;
+
+        self.load_addon(addon_path).await
+    }
+
+    /// Restores add-ons whose install was interrupted after their previous version had been
+    /// moved to the backup directory and removes leftover staging directories.
+    fn recover_interrupted_installs(&self) -> Result<()> {
+        for entry in fs::read_dir(user_config::ADDONS_BACKUP_DIR.as_path())? {
+            let backup_path = entry?.path();
+            if let Some(package_id) = backup_path.file_name() {
+                let addon_path = user_config::ADDONS_DIR.join(package_id);
+                if !addon_path.exists() {
+                    warn!("Restoring interrupted install of {:?}", package_id);
+                    fs::rename(&backup_path, &addon_path)?;
+                }
+            }
+        }
+        for entry in fs::read_dir(user_config::ADDONS_STAGING_DIR.as_path())? {
+            fs::remove_dir_all(entry?.path())?;
+        }
         Ok(())
     }
 
@@ -156,6 +337,9 @@ impl Handler<LoadAddons> for AddonManager {
         _ctx: &mut Context<Self>,
         LoadAddons(addon_dir): LoadAddons,
     ) -> Result<()> {
+        if let Err(err) = self.recover_interrupted_installs() {
+            error!("Failed to recover interrupted installs: {:?}", err);
+        }
         info!("Loading addons from {:?}", addon_dir);
         let entries = fs::read_dir(addon_dir)
             .context(anyhow!("Could not load addons"))?
@@ -201,7 +385,41 @@ pub struct AddonStarted(pub String, pub Addr<AddonInstance>);
 #[async_trait]
 impl Handler<AddonStarted> for AddonManager {
     async fn handle(&mut self, _ctx: &mut Context<Self>, AddonStarted(id, addr): AddonStarted) {
-        self.running_addons.insert(id, addr);
+        if self.pending_installs.remove(&id).is_some() {
+            info!("New version of addon {} registered", id);
+        }
+        let actor_id = addr.actor_id();
+        if let Some(mut previous) = self.running_addons.insert(id.to_owned(), addr) {
+            if previous.actor_id() != actor_id {
+                info!("Addon {} reconnected, replacing its previous instance", id);
+                if let Err(err) = previous.stop(None) {
+                    error!("Failed to stop previous instance of addon {}: {}", id, err);
+                }
+            }
+        }
+    }
+}
+
+/// Sent by an instance whose connection closed. Ignored if the add-on has already been
+/// replaced by a newer instance.
+#[message(result = "()")]
+pub struct AddonDisconnected(pub String, pub u64);
+
+#[async_trait]
+impl Handler<AddonDisconnected> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        AddonDisconnected(id, actor_id): AddonDisconnected,
+    ) {
+        if self
+            .running_addons
+            .get(&id)
+            .map_or(false, |instance| instance.actor_id() == actor_id)
+        {
+            info!("Addon {} disconnected", id);
+            self.running_addons.remove(&id);
+        }
     }
 }
 
@@ -320,51 +538,70 @@ impl Handler<UninstallAddon> for AddonManager {
         _ctx: &mut Context<Self>,
         UninstallAddon(addon_id): UninstallAddon,
     ) -> Result<()> {
-        if let Err(err) = self.unload_addon(addon_id.to_owned()).await {
-            error!("Failed to unload {} properly: {:?}", addon_id, err);
-        }
-        let addon_path = user_config::ADDONS_DIR.join(addon_id.to_owned());
-        if addon_path.exists() && addon_path.is_dir() {
-            fs::remove_dir_all(addon_path).context(format!("Error removing {}", addon_id))?;
+        self.pending_installs.remove(&addon_id);
+        self.uninstall_addon(addon_id.to_owned(), true)
+            .await
+            .context(format!("Failed to uninstall {}", addon_id))?;
+        let backup_path = user_config::ADDONS_BACKUP_DIR.join(addon_id.to_owned());
+        if backup_path.exists() {
+            fs::remove_dir_all(backup_path)
+                .context(format!("Error removing backup of {}", addon_id))?;
         }
-        let enabled_key = format!("addons.{}.enabled", addon_id);
-        call!(Db.SetSetting(enabled_key, false))
-            .context(format!("Failed to disable {}", addon_id))?;
-        self.installed_addons.remove(&addon_id);
         Ok(())
     }
 }
 
-#[message(result = "Result<()>")]
-pub struct InstallAddonFromUrl(pub String, pub String, pub String, pub bool);
+/// Installs a `.tar.gz` package from the local file system and returns the ID of the add-on.
+/// If an ID is given, packages of any other add-on are rejected.
+#[message(result = "Result<String>")]
+pub struct InstallAddonFromFile(pub PathBuf, pub Option<String>, pub bool);
 
 #[async_trait]
-impl Handler<InstallAddonFromUrl> for AddonManager {
+impl Handler<InstallAddonFromFile> for AddonManager {
     async fn handle(
         &mut self,
-        _ctx: &mut Context<Self>,
-        InstallAddonFromUrl(id, url, checksum, enable): InstallAddonFromUrl,
-    ) -> Result<()> {
-        let temp_dir = TempDir::new(&id)?;
-        let dest_path = temp_dir.path().join(format!("{}.tar.gz", id));
+        ctx: &mut Context<Self>,
+        InstallAddonFromFile(path, expected_id, enable): InstallAddonFromFile,
+    ) -> Result<String> {
+        self.install_addon(ctx, expected_id, path, enable).await
+    }
+}
 
-        info!("Fetching add-on {} as {:?}", url, dest_path);
-        let res = reqwest::get(&url).await?.bytes().await?;
-        let mut file = File::create(dest_path.clone())?;
-        file.write_all(res.as_ref())?;
+#[message(result = "()")]
+struct RegistrationTimeout(String, u64);
 
-        if digest_bytes(res.as_ref()) != checksum.to_lowercase() {
-            return Err(anyhow!(format!(
-                "Checksum did not match for add-on: {}",
-                id,
-            )));
+#[async_trait]
+impl Handler<RegistrationTimeout> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RegistrationTimeout(id, install): RegistrationTimeout,
+    ) {
+        if self.pending_installs.get(&id) == Some(&install) {
+            error!("New version of addon {} did not register in time", id);
+            if let Err(err) = self.rollback_addon(id.to_owned()).await {
+                error!("Failed to roll back addon {}: {:?}", id, err);
+            }
         }
-        self.install_addon(id, dest_path, enable).await?;
-        Ok(())
     }
 }
 
-#[message(result = "Result<HashMap<String, DeviceDescription>>")]
+/// Restores the version of an add-on which was installed before the last update.
+#[message(result = "Result<()>")]
+pub struct RollbackAddon(pub String);
+
+#[async_trait]
+impl Handler<RollbackAddon> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RollbackAddon(id): RollbackAddon,
+    ) -> Result<()> {
+        self.rollback_addon(id).await
+    }
+}
+
+#[message(result = "Result<HashMap<String, Device>>")]
 pub struct GetDevices;
 
 #[async_trait]
@@ -373,17 +610,331 @@ impl Handler<GetDevices> for AddonManager {
         &mut self,
         _ctx: &mut Context<Self>,
         _msg: GetDevices,
-    ) -> Result<HashMap<String, DeviceDescription>> {
+    ) -> Result<HashMap<String, Device>> {
         let mut devices = HashMap::new();
-        for instance in self.running_addons.values() {
-            devices.extend(
-                instance
-                    .call(addon_instance::GetDevices)
-                    .await
-                    .map_err(|err| anyhow!(err))
-                    .flatten()?,
-            );
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::GetDevices)
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(instance_devices) => devices.extend(instance_devices),
+                Err(err) => error!("Failed to get devices of add-on {}: {}", addon_id, err),
+            }
         }
         Ok(devices)
     }
 }
+
+#[message(result = "Result<PendingRequest<Value>>")]
+pub struct SetPropertyValue(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<SetPropertyValue> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        SetPropertyValue(device_id, property_name, value): SetPropertyValue,
+    ) -> Result<PendingRequest<Value>> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::SetPropertyValue(
+                device_id,
+                property_name,
+                value,
+            ))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct RemoveDevice(pub String);
+
+#[async_trait]
+impl Handler<RemoveDevice> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RemoveDevice(device_id): RemoveDevice,
+    ) -> Result<()> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::RemoveDevice(device_id))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+#[message(result = "Result<(ActionRequest, PendingRequest<()>)>")]
+pub struct RequestAction(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<RequestAction> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RequestAction(device_id, action_name, input): RequestAction,
+    ) -> Result<(ActionRequest, PendingRequest<()>)> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::RequestAction(device_id, action_name, input))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+#[message(result = "Result<PendingRequest<()>>")]
+pub struct RemoveAction(pub String, pub String, pub String);
+
+#[async_trait]
+impl Handler<RemoveAction> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RemoveAction(device_id, action_name, action_id): RemoveAction,
+    ) -> Result<PendingRequest<()>> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::RemoveAction(
+                device_id,
+                action_name,
+                action_id,
+            ))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct FailAction(pub String, pub String);
+
+#[async_trait]
+impl Handler<FailAction> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        FailAction(device_id, action_id): FailAction,
+    ) -> Result<()> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::FailAction(device_id, action_id))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+/// Waits for the add-on to confirm an action request. Requests which the add-on rejects or does
+/// not confirm in time are marked as failed, so that they don't linger as created.
+pub async fn confirm_action(
+    device_id: &str,
+    action: &ActionRequest,
+    request: PendingRequest<()>,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let result = request.wait(timeout).await;
+    if result.is_err() {
+        if let Err(err) = call!(AddonManager.FailAction(device_id.to_owned(), action.id.to_owned()))
+        {
+            error!(
+                "Failed to mark action request {} as failed: {}",
+                action.id, err
+            );
+        }
+    }
+    result
+}
+
+#[message(result = "Result<Vec<ActionRequest>>")]
+pub struct GetActions(pub String);
+
+#[async_trait]
+impl Handler<GetActions> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetActions(device_id): GetActions,
+    ) -> Result<Vec<ActionRequest>> {
+        let instance = self.get_instance_of_device(&device_id).await?;
+        instance
+            .call(addon_instance::GetActions(device_id))
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+/// Starts a new pairing session on all running add-ons, replacing any active one.
+///
+/// Returns the id of the session, which ends automatically after the given timeout.
+#[message(result = "Result<u64>")]
+pub struct StartPairing(pub Duration);
+
+#[async_trait]
+impl Handler<StartPairing> for AddonManager {
+    async fn handle(
+        &mut self,
+        ctx: &mut Context<Self>,
+        StartPairing(timeout): StartPairing,
+    ) -> Result<u64> {
+        self.next_pairing_session += 1;
+        let session = self.next_pairing_session;
+        info!("Starting pairing session {} for {:?}", session, timeout);
+        for (addon_id, instance) in &self.running_addons {
+            if let Err(err) = instance
+                .call(addon_instance::StartPairing(timeout))
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                error!("Failed to start pairing of add-on {}: {}", addon_id, err);
+            }
+        }
+        self.pairing_session = Some(session);
+        ctx.send_later(PairingTimeout(session), timeout);
+        Ok(session)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct CancelPairing;
+
+#[async_trait]
+impl Handler<CancelPairing> for AddonManager {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: CancelPairing) -> Result<()> {
+        self.cancel_pairing().await;
+        Ok(())
+    }
+}
+
+#[message(result = "()")]
+struct PairingTimeout(u64);
+
+#[async_trait]
+impl Handler<PairingTimeout> for AddonManager {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, PairingTimeout(session): PairingTimeout) {
+        if self.pairing_session == Some(session) {
+            self.cancel_pairing().await;
+        }
+    }
+}
+
+#[message(result = "Result<Vec<NotifierDescription>>")]
+pub struct GetNotifiers;
+
+#[async_trait]
+impl Handler<GetNotifiers> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetNotifiers,
+    ) -> Result<Vec<NotifierDescription>> {
+        let mut notifiers = Vec::new();
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::GetNotifiers)
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(instance_notifiers) => notifiers.extend(instance_notifiers),
+                Err(err) => error!("Failed to get notifiers of add-on {}: {}", addon_id, err),
+            }
+        }
+        Ok(notifiers)
+    }
+}
+
+#[message(result = "Result<Option<Vec<OutletDescription>>>")]
+pub struct GetOutlets(pub String);
+
+#[async_trait]
+impl Handler<GetOutlets> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetOutlets(notifier_id): GetOutlets,
+    ) -> Result<Option<Vec<OutletDescription>>> {
+        for (addon_id, instance) in &self.running_addons {
+            match instance
+                .call(addon_instance::GetOutlets(notifier_id.to_owned()))
+                .await
+                .map_err(|err| anyhow!(err))
+                .flatten()
+            {
+                Ok(Some(outlets)) => return Ok(Some(outlets)),
+                Ok(None) => {}
+                Err(err) => error!("Failed to query notifiers of add-on {}: {}", addon_id, err),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[message(result = "Result<PendingRequest<()>>")]
+pub struct Notify {
+    pub notifier_id: String,
+    pub outlet_id: String,
+    pub title: String,
+    pub message: String,
+    pub level: i64,
+}
+
+#[async_trait]
+impl Handler<Notify> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: Notify,
+    ) -> Result<PendingRequest<()>> {
+        let instance = self.get_instance_of_notifier(&msg.notifier_id).await?;
+        instance
+            .call(addon_instance::Notify {
+                notifier_id: msg.notifier_id,
+                outlet_id: msg.outlet_id,
+                title: msg.title,
+                message: msg.message,
+                level: msg.level,
+            })
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
+
+#[message(result = "Result<PendingRequest<ApiResponse>>")]
+pub struct ApiRequest {
+    pub package_name: String,
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, Value>,
+    pub body: BTreeMap<String, Value>,
+}
+
+#[async_trait]
+impl Handler<ApiRequest> for AddonManager {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ApiRequest,
+    ) -> Result<PendingRequest<ApiResponse>> {
+        let instance = self.get_instance_of_api_handler(&msg.package_name).await?;
+        instance
+            .call(addon_instance::ApiRequest {
+                package_name: msg.package_name,
+                method: msg.method,
+                path: msg.path,
+                query: msg.query,
+                body: msg.body,
+            })
+            .await
+            .map_err(|err| anyhow!(err))
+            .flatten()
+    }
+}
@@ -4,7 +4,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    addon_instance::{AddonInstance, Msg},
+    addon_instance::{AddonInstance, Disconnect, Msg},
     config::CONFIG,
 };
 use anyhow::{anyhow, Result};
@@ -28,10 +28,22 @@ async fn handle_connection(stream: TcpStream, addr: SocketAddr) {
         .expect("Start addon instance");
 
     while let Some(msg) = stream.next().await {
-        let msg = msg.expect("Receive message");
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                error!("Failed to receive message from {}: {}", addr, err);
+                break;
+            }
+        };
         if let tungstenite::Message::Text(msg) = msg {
             debug!("Received a message from {}: {}", addr, msg);
-            let msg = msg.parse::<Message>().unwrap();
+            let msg = match msg.parse::<Message>() {
+                Ok(msg) => msg,
+                Err(err) => {
+                    error!("Failed to parse message from {}: {}", addr, err);
+                    continue;
+                }
+            };
             let id = msg.plugin_id().to_owned();
 
             if let Err(err) = addon_instance
@@ -42,10 +54,22 @@ async fn handle_connection(stream: TcpStream, addr: SocketAddr) {
             {
                 error!("Addon instance {:?} failed to handle message: {}", id, err);
             }
+        } else if let tungstenite::Message::Close(_) = msg {
+            break;
         } else {
             warn!("Received unexpected message")
         }
     }
+
+    info!("Addon connection from {} closed", addr);
+    if let Err(err) = addon_instance
+        .call(Disconnect)
+        .await
+        .map_err(|err| anyhow!(err))
+        .flatten()
+    {
+        debug!("Addon instance was already stopped: {}", err);
+    }
 }
 
 pub async fn start() -> Result<()> {
@@ -3,11 +3,20 @@ use figment::{
     Figment,
 };
 use serde::Deserialize;
+use std::env;
+
+fn default_registration_timeout() -> u64 {
+    30
+}
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddonManager {
     pub list_urls: Vec<String>,
+    /// Seconds a newly installed add-on has to register before the previous version is
+    /// restored.
+    #[serde(default = "default_registration_timeout")]
+    pub registration_timeout: u64,
 }
 
 #[derive(Deserialize)]
@@ -23,13 +32,16 @@ pub struct Ports {
 pub struct Config {
     pub addon_manager: AddonManager,
     pub ports: Ports,
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
 }
 
 lazy_static! {
     pub static ref CONFIG: Config = {
-        Figment::new()
-            .merge(Json::file("Config.json"))
-            .extract()
-            .expect("Read config")
+        let mut figment = Figment::new().merge(Json::file("Config.json"));
+        if let Ok(overrides) = env::var("WEBTHINGS_CONFIG") {
+            figment = figment.merge(Json::string(&overrides));
+        }
+        figment.extract().expect("Read config")
     };
 }
@@ -4,15 +4,22 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    model::{IntoThing, Thing, ThingWithoutId, User},
+    model::{
+        IntoThing, Rule, RuleWithoutId, Scene, SceneWithoutId, Schedule, ScheduleWithoutId,
+        StoredPropertyValue, Thing, ThingEvent, ThingWithoutId, User,
+    },
     user_config,
 };
 use anyhow::{anyhow, Context as AnyhowContext, Result};
 use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, fmt::Debug, marker::PhantomData, ops::Deref, str::FromStr};
 use webthings_gateway_ipc_types::Device;
 use xactor::{message, Actor, Context, Handler, Message, Service};
 
+/// Number of events kept per thing before the oldest ones are dropped.
+const MAX_EVENTS_PER_THING: i64 = 100;
+
 pub struct Db(Connection);
 
 impl Actor for Db {}
@@ -97,6 +104,10 @@ impl Handler<CreateThing> for Db {
         let thing = Thing {
             device: description,
             connected: true,
+            selected_capability: None,
+            floorplan_x: None,
+            floorplan_y: None,
+            stale_properties: None,
         };
         let description = serde_json::to_string(&thing).context("Stringify thing")?;
         self.execute(
@@ -108,6 +119,539 @@ impl Handler<CreateThing> for Db {
     }
 }
 
+#[message(result = "Result<()>")]
+pub struct CreateEvent(pub String, pub ThingEvent);
+
+#[async_trait]
+impl Handler<CreateEvent> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateEvent(thing_id, event): CreateEvent,
+    ) -> Result<()> {
+        let data = serde_json::to_string(&event.data).context("Stringify event data")?;
+        self.execute(
+            "INSERT INTO events (thingId, name, data, timestamp) VALUES (?, ?, ?, ?)",
+            params![thing_id, event.name, data, event.timestamp],
+        )
+        .context("Insert into events")?;
+        self.execute(
+            "DELETE FROM events WHERE thingId = ?1 AND id NOT IN
+                    (SELECT id FROM events WHERE thingId = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![thing_id, MAX_EVENTS_PER_THING],
+        )
+        .context("Prune events")?;
+        Ok(())
+    }
+}
+
+#[message(result = "Result<Vec<ThingEvent>>")]
+pub struct GetEvents(pub String, pub Option<String>);
+
+#[async_trait]
+impl Handler<GetEvents> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetEvents(thing_id, name): GetEvents,
+    ) -> Result<Vec<ThingEvent>> {
+        let mut stmt = self
+            .prepare(
+                "SELECT name, data, timestamp FROM events
+                    WHERE thingId = ?1 AND (?2 IS NULL OR name = ?2) ORDER BY id",
+            )
+            .context("Prepare statement")?;
+        let mut rows = stmt
+            .query(params![thing_id, name])
+            .context("Execute query")?;
+        let mut events = Vec::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            let name: String = row.get(0).context("Get parameter")?;
+            let data: String = row.get(1).context("Get parameter")?;
+            let timestamp: String = row.get(2).context("Get parameter")?;
+            events.push(ThingEvent {
+                name,
+                data: serde_json::from_str(&data).context("Parse JSON event data")?,
+                timestamp,
+            });
+        }
+        Ok(events)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct StorePropertyValue(pub String, pub String, pub StoredPropertyValue);
+
+#[async_trait]
+impl Handler<StorePropertyValue> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        StorePropertyValue(thing_id, name, value): StorePropertyValue,
+    ) -> Result<()> {
+        let json = serde_json::to_string(&value.value).context("Stringify property value")?;
+        self.execute(
+            "INSERT OR REPLACE INTO property_values (thingId, name, value, timestamp)
+                VALUES (?, ?, ?, ?)",
+            params![thing_id, name, json, value.timestamp],
+        )
+        .context("Insert into property_values")?;
+        Ok(())
+    }
+}
+
+#[message(result = "Result<HashMap<String, StoredPropertyValue>>")]
+pub struct GetPropertyValues(pub String);
+
+#[async_trait]
+impl Handler<GetPropertyValues> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetPropertyValues(thing_id): GetPropertyValues,
+    ) -> Result<HashMap<String, StoredPropertyValue>> {
+        let mut stmt = self
+            .prepare("SELECT name, value, timestamp FROM property_values WHERE thingId = ?")
+            .context("Prepare statement")?;
+        let mut rows = stmt.query(params![thing_id]).context("Execute query")?;
+        let mut values = HashMap::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            let name: String = row.get(0).context("Get parameter")?;
+            let value: String = row.get(1).context("Get parameter")?;
+            let timestamp: String = row.get(2).context("Get parameter")?;
+            values.insert(
+                name,
+                StoredPropertyValue {
+                    value: serde_json::from_str(&value).context("Parse JSON property value")?,
+                    timestamp,
+                },
+            );
+        }
+        Ok(values)
+    }
+}
+
+#[message(result = "Result<HashMap<String, HashMap<String, StoredPropertyValue>>>")]
+pub struct GetAllPropertyValues;
+
+#[async_trait]
+impl Handler<GetAllPropertyValues> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetAllPropertyValues,
+    ) -> Result<HashMap<String, HashMap<String, StoredPropertyValue>>> {
+        let mut stmt = self
+            .prepare("SELECT thingId, name, value, timestamp FROM property_values")
+            .context("Prepare statement")?;
+        let mut rows = stmt.query([]).context("Execute query")?;
+        let mut values: HashMap<String, HashMap<String, StoredPropertyValue>> = HashMap::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            let thing_id: String = row.get(0).context("Get parameter")?;
+            let name: String = row.get(1).context("Get parameter")?;
+            let value: String = row.get(2).context("Get parameter")?;
+            let timestamp: String = row.get(3).context("Get parameter")?;
+            values.entry(thing_id).or_default().insert(
+                name,
+                StoredPropertyValue {
+                    value: serde_json::from_str(&value).context("Parse JSON property value")?,
+                    timestamp,
+                },
+            );
+        }
+        Ok(values)
+    }
+}
+
+/// An entity which is stored as a JSON description in a table of its own, keyed by an id which
+/// the database assigns.
+trait JsonTable: Sized {
+    type Description: Serialize + DeserializeOwned;
+
+    const TABLE: &'static str;
+    const KIND: &'static str;
+
+    fn new(id: i64, description: Self::Description) -> Self;
+    fn id(&self) -> i64;
+    fn description(&self) -> &Self::Description;
+}
+
+impl JsonTable for Rule {
+    type Description = RuleWithoutId;
+
+    const TABLE: &'static str = "rules";
+    const KIND: &'static str = "Rule";
+
+    fn new(id: i64, description: RuleWithoutId) -> Self {
+        Self { id, description }
+    }
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn description(&self) -> &RuleWithoutId {
+        &self.description
+    }
+}
+
+impl JsonTable for Schedule {
+    type Description = ScheduleWithoutId;
+
+    const TABLE: &'static str = "schedules";
+    const KIND: &'static str = "Schedule";
+
+    fn new(id: i64, description: ScheduleWithoutId) -> Self {
+        Self { id, description }
+    }
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn description(&self) -> &ScheduleWithoutId {
+        &self.description
+    }
+}
+
+impl JsonTable for Scene {
+    type Description = SceneWithoutId;
+
+    const TABLE: &'static str = "scenes";
+    const KIND: &'static str = "Scene";
+
+    fn new(id: i64, description: SceneWithoutId) -> Self {
+        Self { id, description }
+    }
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn description(&self) -> &SceneWithoutId {
+        &self.description
+    }
+}
+
+impl Db {
+    fn get_entries<T: JsonTable>(&self) -> Result<Vec<T>> {
+        let mut stmt = self
+            .prepare(&format!(
+                "SELECT id, description FROM {} ORDER BY id",
+                T::TABLE
+            ))
+            .context("Prepare statement")?;
+        let mut rows = stmt.query([]).context("Execute query")?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            let id: i64 = row.get(0).context("Get parameter")?;
+            let description: String = row.get(1).context("Get parameter")?;
+            entries.push(T::new(
+                id,
+                serde_json::from_str(&description).context("Parse JSON description")?,
+            ));
+        }
+        Ok(entries)
+    }
+
+    fn get_entry<T: JsonTable>(&self, id: i64) -> Result<Option<T>> {
+        let description: Option<String> = self
+            .query_row(
+                &format!("SELECT description FROM {} WHERE id = ?", T::TABLE),
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Query database")?;
+        match description {
+            None => Ok(None),
+            Some(description) => Ok(Some(T::new(
+                id,
+                serde_json::from_str(&description).context("Parse JSON description")?,
+            ))),
+        }
+    }
+
+    fn create_entry<T: JsonTable>(&self, description: T::Description) -> Result<T> {
+        let json = serde_json::to_string(&description).context("Stringify description")?;
+        self.execute(
+            &format!("INSERT INTO {} (description) VALUES (?)", T::TABLE),
+            params![json],
+        )
+        .with_context(|| format!("Insert into {}", T::TABLE))?;
+        Ok(T::new(self.last_insert_rowid(), description))
+    }
+
+    fn update_entry<T: JsonTable>(&self, entry: &T) -> Result<()> {
+        let json = serde_json::to_string(entry.description()).context("Stringify description")?;
+        let updated = self
+            .execute(
+                &format!("UPDATE {} SET description = ? WHERE id = ?", T::TABLE),
+                params![json, entry.id()],
+            )
+            .with_context(|| format!("Update {}", T::TABLE))?;
+        if updated == 0 {
+            return Err(anyhow!("{} {} does not exist", T::KIND, entry.id()));
+        }
+        Ok(())
+    }
+
+    fn delete_entry<T: JsonTable>(&self, id: i64) -> Result<()> {
+        let deleted = self
+            .execute(
+                &format!("DELETE FROM {} WHERE id = ?", T::TABLE),
+                params![id],
+            )
+            .with_context(|| format!("Delete from {}", T::TABLE))?;
+        if deleted == 0 {
+            return Err(anyhow!("{} {} does not exist", T::KIND, id));
+        }
+        Ok(())
+    }
+}
+
+#[message(result = "Result<Vec<Rule>>")]
+pub struct GetRules;
+
+#[async_trait]
+impl Handler<GetRules> for Db {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: GetRules) -> Result<Vec<Rule>> {
+        self.get_entries()
+    }
+}
+
+#[message(result = "Result<Option<Rule>>")]
+pub struct GetRule(pub i64);
+
+#[async_trait]
+impl Handler<GetRule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetRule(id): GetRule,
+    ) -> Result<Option<Rule>> {
+        self.get_entry(id)
+    }
+}
+
+#[message(result = "Result<Rule>")]
+pub struct CreateRule(pub RuleWithoutId);
+
+#[async_trait]
+impl Handler<CreateRule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateRule(description): CreateRule,
+    ) -> Result<Rule> {
+        self.create_entry(description)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct UpdateRule(pub Rule);
+
+#[async_trait]
+impl Handler<UpdateRule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        UpdateRule(rule): UpdateRule,
+    ) -> Result<()> {
+        self.update_entry(&rule)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct DeleteRule(pub i64);
+
+#[async_trait]
+impl Handler<DeleteRule> for Db {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, DeleteRule(id): DeleteRule) -> Result<()> {
+        self.delete_entry::<Rule>(id)
+    }
+}
+
+#[message(result = "Result<Vec<Schedule>>")]
+pub struct GetSchedules;
+
+#[async_trait]
+impl Handler<GetSchedules> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetSchedules,
+    ) -> Result<Vec<Schedule>> {
+        self.get_entries()
+    }
+}
+
+#[message(result = "Result<Option<Schedule>>")]
+pub struct GetSchedule(pub i64);
+
+#[async_trait]
+impl Handler<GetSchedule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetSchedule(id): GetSchedule,
+    ) -> Result<Option<Schedule>> {
+        self.get_entry(id)
+    }
+}
+
+#[message(result = "Result<Schedule>")]
+pub struct CreateSchedule(pub ScheduleWithoutId);
+
+#[async_trait]
+impl Handler<CreateSchedule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateSchedule(description): CreateSchedule,
+    ) -> Result<Schedule> {
+        self.create_entry(description)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct UpdateSchedule(pub Schedule);
+
+#[async_trait]
+impl Handler<UpdateSchedule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        UpdateSchedule(schedule): UpdateSchedule,
+    ) -> Result<()> {
+        self.update_entry(&schedule)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct DeleteSchedule(pub i64);
+
+#[async_trait]
+impl Handler<DeleteSchedule> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        DeleteSchedule(id): DeleteSchedule,
+    ) -> Result<()> {
+        self.delete_entry::<Schedule>(id)
+    }
+}
+
+#[message(result = "Result<Vec<Scene>>")]
+pub struct GetScenes;
+
+#[async_trait]
+impl Handler<GetScenes> for Db {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: GetScenes) -> Result<Vec<Scene>> {
+        self.get_entries()
+    }
+}
+
+#[message(result = "Result<Option<Scene>>")]
+pub struct GetScene(pub i64);
+
+#[async_trait]
+impl Handler<GetScene> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        GetScene(id): GetScene,
+    ) -> Result<Option<Scene>> {
+        self.get_entry(id)
+    }
+}
+
+#[message(result = "Result<Scene>")]
+pub struct CreateScene(pub SceneWithoutId);
+
+#[async_trait]
+impl Handler<CreateScene> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateScene(description): CreateScene,
+    ) -> Result<Scene> {
+        self.create_entry(description)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct UpdateScene(pub Scene);
+
+#[async_trait]
+impl Handler<UpdateScene> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        UpdateScene(scene): UpdateScene,
+    ) -> Result<()> {
+        self.update_entry(&scene)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct DeleteScene(pub i64);
+
+#[async_trait]
+impl Handler<DeleteScene> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        DeleteScene(id): DeleteScene,
+    ) -> Result<()> {
+        self.delete_entry::<Scene>(id)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct UpdateThing(pub Thing);
+
+#[async_trait]
+impl Handler<UpdateThing> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        UpdateThing(thing): UpdateThing,
+    ) -> Result<()> {
+        let description = serde_json::to_string(&thing).context("Stringify thing")?;
+        let updated = self
+            .execute(
+                "UPDATE things SET description = ? WHERE id = ?",
+                params![description, thing.id],
+            )
+            .context("Update thing")?;
+        if updated == 0 {
+            return Err(anyhow!("Thing {} does not exist", thing.id));
+        }
+        Ok(())
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct DeleteThing(pub String);
+
+#[async_trait]
+impl Handler<DeleteThing> for Db {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        DeleteThing(id): DeleteThing,
+    ) -> Result<()> {
+        self.execute("DELETE FROM things WHERE id = ?", params![id])
+            .context("Delete thing")?;
+        self.execute("DELETE FROM events WHERE thingId = ?", params![id])
+            .context("Delete events of thing")?;
+        self.execute("DELETE FROM property_values WHERE thingId = ?", params![id])
+            .context("Delete property values of thing")?;
+        Ok(())
+    }
+}
+
 pub struct SetSetting<T>(pub String, pub T);
 
 impl<T: Send + 'static> Message for SetSetting<T> {
@@ -435,22 +979,74 @@ fn create_tables(conn: &Connection) {
         [],
     )
     .expect("Create table jsonwebtokens");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS events(
+                  id INTEGER PRIMARY KEY ASC,
+                  thingId TEXT,
+                  name TEXT,
+                  data TEXT,
+                  timestamp TEXT
+                  )",
+        [],
+    )
+    .expect("Create table events");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS property_values(
+                  thingId TEXT,
+                  name TEXT,
+                  value TEXT,
+                  timestamp TEXT,
+                  PRIMARY KEY (thingId, name)
+                  )",
+        [],
+    )
+    .expect("Create table property_values");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rules(
+                  id INTEGER PRIMARY KEY ASC,
+                  description TEXT
+                  )",
+        [],
+    )
+    .expect("Create table rules");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schedules(
+                  id INTEGER PRIMARY KEY ASC,
+                  description TEXT
+                  )",
+        [],
+    )
+    .expect("Create table schedules");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scenes(
+                  id INTEGER PRIMARY KEY ASC,
+                  description TEXT
+                  )",
+        [],
+    )
+    .expect("Create table scenes");
 }
 
 #[cfg(test)]
 mod tests {
     extern crate two_rusty_forks;
     use super::*;
-    use crate::{macros::call, model::IntoDevice, tests_common::setup};
+    use crate::{
+        macros::call,
+        model::{Effect, IntoDevice, SceneValues, Timing, Trigger},
+        tests_common::setup,
+    };
     use serde_json::json;
     use two_rusty_forks::test_fork;
     use webthings_gateway_ipc_types::DeviceWithoutId;
 
-    #[async_test]
-    #[test_fork]
-    async fn test_create_things() {
-        let _ = setup();
-        let description = DeviceWithoutId {
+    fn device_description() -> DeviceWithoutId {
+        DeviceWithoutId {
             at_context: None,
             at_type: None,
             actions: None,
@@ -462,7 +1058,33 @@ mod tests {
             pin: None,
             properties: None,
             title: None,
-        };
+        }
+    }
+
+    /// Stores an entry, changes it and deletes it again, checking the table after every step.
+    fn check_json_table<T>(description: T::Description, change: impl FnOnce(&mut T))
+    where
+        T: JsonTable + Clone + PartialEq + Debug,
+    {
+        let db = Db::default();
+        let mut entry = db.create_entry::<T>(description).unwrap();
+        assert_eq!(db.get_entries::<T>().unwrap(), vec![entry.clone()]);
+
+        change(&mut entry);
+        db.update_entry(&entry).unwrap();
+        assert_eq!(db.get_entry::<T>(entry.id()).unwrap(), Some(entry.clone()));
+
+        db.delete_entry::<T>(entry.id()).unwrap();
+        assert_eq!(db.get_entry::<T>(entry.id()).unwrap(), None);
+        assert!(db.update_entry(&entry).is_err());
+        assert!(db.delete_entry::<T>(entry.id()).is_err());
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_create_things() {
+        let _ = setup();
+        let description = device_description();
         call!(Db.CreateThing(description.clone().into_device("test1".to_owned()))).unwrap();
         call!(Db.CreateThing(description.clone().into_device("test2".to_owned()))).unwrap();
         let things = call!(Db.GetThings).unwrap();
@@ -471,7 +1093,11 @@ mod tests {
             things[0],
             ThingWithoutId {
                 device: description.clone(),
-                connected: true
+                connected: true,
+                selected_capability: None,
+                floorplan_x: None,
+                floorplan_y: None,
+                stale_properties: None,
             }
             .into_thing("test1".to_owned())
         );
@@ -479,7 +1105,11 @@ mod tests {
             things[1],
             ThingWithoutId {
                 device: description,
-                connected: true
+                connected: true,
+                selected_capability: None,
+                floorplan_x: None,
+                floorplan_y: None,
+                stale_properties: None,
             }
             .into_thing("test2".to_owned())
         );
@@ -489,19 +1119,7 @@ mod tests {
     #[test_fork]
     async fn test_get_thing() {
         let _ = setup();
-        let description = DeviceWithoutId {
-            at_context: None,
-            at_type: None,
-            actions: None,
-            base_href: None,
-            credentials_required: None,
-            description: None,
-            events: None,
-            links: None,
-            pin: None,
-            properties: None,
-            title: None,
-        };
+        let description = device_description();
         call!(Db.CreateThing(description.clone().into_device("test".to_owned()))).unwrap();
         let thing = call!(Db.GetThing("test".to_owned())).unwrap();
         assert_eq!(
@@ -510,12 +1128,212 @@ mod tests {
                 ThingWithoutId {
                     device: description,
                     connected: true,
+                    selected_capability: None,
+                    floorplan_x: None,
+                    floorplan_y: None,
+                    stale_properties: None,
                 }
                 .into_thing("test".to_owned())
             )
         );
     }
 
+    #[async_test]
+    #[test_fork]
+    async fn test_update_thing() {
+        let _ = setup();
+        let description = device_description();
+        let mut thing =
+            call!(Db.CreateThing(description.clone().into_device("test".to_owned()))).unwrap();
+        thing.device.title = Some("Renamed".to_owned());
+        thing.floorplan_x = Some(10.0);
+        thing.floorplan_y = Some(20.0);
+        call!(Db.UpdateThing(thing)).unwrap();
+        let thing = call!(Db.GetThing("test".to_owned())).unwrap().unwrap();
+        assert_eq!(thing.title, Some("Renamed".to_owned()));
+        assert_eq!(thing.floorplan_x, Some(10.0));
+        assert_eq!(thing.floorplan_y, Some(20.0));
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_update_unknown_thing() {
+        let _ = setup();
+        let description = device_description();
+        let thing = ThingWithoutId {
+            device: description,
+            connected: true,
+            selected_capability: None,
+            floorplan_x: None,
+            floorplan_y: None,
+            stale_properties: None,
+        }
+        .into_thing("test".to_owned());
+        assert!(call!(Db.UpdateThing(thing)).is_err());
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_delete_thing() {
+        let _ = setup();
+        let description = device_description();
+        call!(Db.CreateThing(description.clone().into_device("test1".to_owned()))).unwrap();
+        call!(Db.CreateThing(description.into_device("test2".to_owned()))).unwrap();
+        call!(Db.DeleteThing("test1".to_owned())).unwrap();
+        assert_eq!(call!(Db.GetThing("test1".to_owned())).unwrap(), None);
+        assert_eq!(call!(Db.GetThings).unwrap().len(), 1);
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_get_events() {
+        let _ = setup();
+        let event = |name: &str, data| ThingEvent {
+            name: name.to_owned(),
+            data,
+            timestamp: "2021-01-01T00:00:00+00:00".to_owned(),
+        };
+        call!(Db.CreateEvent("test1".to_owned(), event("motion", json!(true)))).unwrap();
+        call!(Db.CreateEvent("test1".to_owned(), event("ring", json!(null)))).unwrap();
+        call!(Db.CreateEvent("test2".to_owned(), event("motion", json!(false)))).unwrap();
+        assert_eq!(
+            call!(Db.GetEvents("test1".to_owned(), None)).unwrap(),
+            vec![event("motion", json!(true)), event("ring", json!(null))]
+        );
+        assert_eq!(
+            call!(Db.GetEvents("test1".to_owned(), Some("ring".to_owned()))).unwrap(),
+            vec![event("ring", json!(null))]
+        );
+        assert_eq!(
+            call!(Db.GetEvents("test2".to_owned(), None)).unwrap(),
+            vec![event("motion", json!(false))]
+        );
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_events_are_bounded() {
+        let _ = setup();
+        for i in 0..MAX_EVENTS_PER_THING + 5 {
+            call!(Db.CreateEvent(
+                "test".to_owned(),
+                ThingEvent {
+                    name: "counter".to_owned(),
+                    data: json!(i),
+                    timestamp: "2021-01-01T00:00:00+00:00".to_owned(),
+                }
+            ))
+            .unwrap();
+        }
+        let events = call!(Db.GetEvents("test".to_owned(), None)).unwrap();
+        assert_eq!(events.len() as i64, MAX_EVENTS_PER_THING);
+        assert_eq!(events[0].data, json!(5));
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_property_values() {
+        let _ = setup();
+        let value = |value, timestamp: &str| StoredPropertyValue {
+            value,
+            timestamp: timestamp.to_owned(),
+        };
+        call!(Db.StorePropertyValue(
+            "test".to_owned(),
+            "on".to_owned(),
+            value(json!(false), "2021-01-01T00:00:00+00:00")
+        ))
+        .unwrap();
+        call!(Db.StorePropertyValue(
+            "test".to_owned(),
+            "on".to_owned(),
+            value(json!(true), "2021-01-02T00:00:00+00:00")
+        ))
+        .unwrap();
+        call!(Db.StorePropertyValue(
+            "test".to_owned(),
+            "level".to_owned(),
+            value(json!(42), "2021-01-01T00:00:00+00:00")
+        ))
+        .unwrap();
+        let values = call!(Db.GetPropertyValues("test".to_owned())).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            values["on"],
+            value(json!(true), "2021-01-02T00:00:00+00:00")
+        );
+        assert_eq!(
+            values["level"],
+            value(json!(42), "2021-01-01T00:00:00+00:00")
+        );
+        assert!(call!(Db.GetPropertyValues("other".to_owned()))
+            .unwrap()
+            .is_empty());
+        let all_values = call!(Db.GetAllPropertyValues).unwrap();
+        assert_eq!(all_values.len(), 1);
+        assert_eq!(all_values["test"], values);
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_rules() {
+        let _ = setup();
+        let description = RuleWithoutId {
+            name: "Night light".to_owned(),
+            enabled: true,
+            trigger: Trigger::PropertyEquals {
+                thing: "switch".to_owned(),
+                property: "on".to_owned(),
+                value: json!(true),
+            },
+            effects: vec![Effect::SetProperty {
+                thing: "lamp".to_owned(),
+                property: "on".to_owned(),
+                value: json!(true),
+            }],
+        };
+        check_json_table::<Rule>(description, |rule| rule.description.enabled = false);
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_schedules() {
+        let _ = setup();
+        let description = ScheduleWithoutId {
+            name: "Porch light".to_owned(),
+            enabled: true,
+            timing: Timing::Sunset { offset: -15 },
+            effects: vec![Effect::SetProperty {
+                thing: "porch".to_owned(),
+                property: "on".to_owned(),
+                value: json!(true),
+            }],
+        };
+        check_json_table::<Schedule>(description, |schedule| {
+            schedule.description.timing = Timing::Cron {
+                expression: "0 30 7 * * *".to_owned(),
+            }
+        });
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_scenes() {
+        let _ = setup();
+        let mut values = SceneValues::new();
+        values
+            .entry("lamp".to_owned())
+            .or_default()
+            .insert("on".to_owned(), json!(true));
+        let description = SceneWithoutId {
+            name: "Movie night".to_owned(),
+            values,
+        };
+        check_json_table::<Scene>(description, |scene| {
+            scene.description.name = "Reading".to_owned()
+        });
+    }
+
     #[async_test]
     #[test_fork]
     async fn test_get_user_by_id() {
@@ -3,17 +3,51 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::{
+    db::{CreateEvent, Db, StorePropertyValue},
+    logs::{Logs, RecordSample},
     macros::send,
-    things_socket::{PropertyStatusMessage, ThingsMessage, ThingsMessages, ThingsSocket},
+    model::{ActionRequest, StoredPropertyValue, ThingEvent},
+    rules::{EventFired, PropertyChanged, RulesEngine},
+    schema,
+    things_socket::{
+        ActionStatusMessage, EventMessage, PropertyStatusMessage, ThingsMessage, ThingsMessages,
+        ThingsSocket,
+    },
 };
 use anyhow::{anyhow, Result};
+use chrono::Utc;
 use log::debug;
 use rocket::serde::json::Value;
-use webthings_gateway_ipc_types::{Device as DeviceDescription, Property};
+use webthings_gateway_ipc_types::{
+    ActionDescription, Device as DeviceDescription, EventDescription, Property,
+};
+
+/// How many completed action requests are kept per device.
+const MAX_COMPLETED_ACTIONS: usize = 20;
+
+fn is_completed(action: &ActionRequest) -> bool {
+    action.time_completed.is_some() || action.status == "completed"
+}
+
+/// Drops the oldest completed action requests beyond `MAX_COMPLETED_ACTIONS`.
+fn prune_completed_actions(actions: &mut Vec<ActionRequest>) {
+    let completed = actions.iter().filter(|action| is_completed(action)).count();
+    let mut excess = completed.saturating_sub(MAX_COMPLETED_ACTIONS);
+    actions.retain(|action| {
+        if excess > 0 && is_completed(action) {
+            excess -= 1;
+            false
+        } else {
+            true
+        }
+    });
+}
 
+#[derive(Clone)]
 pub struct Device {
     pub description: DeviceDescription,
     connected: bool,
+    actions: Vec<ActionRequest>,
 }
 
 impl Device {
@@ -38,13 +72,17 @@ impl Device {
                 name, id, property.value, new_property.value
             );
 
+            let value = new_property.value.clone().unwrap_or(Value::Null);
+            send!(Db.StorePropertyValue(
+                id.clone(),
+                name.clone(),
+                StoredPropertyValue::new(value.clone())
+            ))?;
+            send!(Logs.RecordSample(id.clone(), name.clone(), value.clone()))?;
+            send!(RulesEngine.PropertyChanged(id.clone(), name.clone(), value.clone()))?;
             send!(
                 ThingsSocket.ThingsMessage(ThingsMessages::PropertyStatusMessage(
-                    PropertyStatusMessage::new(
-                        id,
-                        name.clone(),
-                        new_property.value.clone().unwrap_or(Value::Null)
-                    )
+                    PropertyStatusMessage::new(id, name.clone(), value)
                 ))
             )?;
         }
@@ -52,9 +90,124 @@ impl Device {
         Ok(())
     }
 
+    pub(crate) async fn notify_event(&self, event: EventDescription) -> Result<()> {
+        let id = self.description.id.clone();
+        debug!("Device {} emitted event {}", id, event.name);
+
+        let event = ThingEvent {
+            name: event.name,
+            data: event.data.unwrap_or(Value::Null),
+            timestamp: event.timestamp,
+        };
+        send!(Db.CreateEvent(id.clone(), event.clone()))?;
+        send!(RulesEngine.EventFired(id.clone(), event.name.clone()))?;
+        send!(
+            ThingsSocket.ThingsMessage(ThingsMessages::EventMessage(EventMessage::new(id, event)))
+        )?;
+        Ok(())
+    }
+
+    /// Checks a value a client wants to write against the description of the property.
+    pub fn validate_property_value(&self, name: &str, value: &Value) -> Result<()> {
+        let property = self
+            .description
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.get(name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Device {} has no property called {}",
+                    self.description.id,
+                    name
+                )
+            })?;
+        schema::validate_write(&serde_json::to_value(property)?, value)
+    }
+
     pub fn set_connect_state(&mut self, state: bool) {
         self.connected = state;
     }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn actions(&self) -> &[ActionRequest] {
+        &self.actions
+    }
+
+    pub async fn add_action(&mut self, action: ActionRequest) -> Result<()> {
+        self.notify_action_status(action.clone()).await?;
+        self.actions.push(action);
+        Ok(())
+    }
+
+    pub async fn update_action(&mut self, description: ActionDescription) -> Result<()> {
+        let action = match self
+            .actions
+            .iter_mut()
+            .find(|action| action.id == description.id)
+        {
+            Some(action) => {
+                debug!(
+                    "Action {} of device {} changed from {} to {}",
+                    action.id, self.description.id, action.status, description.status
+                );
+                action.status = description.status;
+                action.time_completed = description.time_completed;
+                let action = action.clone();
+                prune_completed_actions(&mut self.actions);
+                action
+            }
+            None => {
+                let mut action = ActionRequest::new(
+                    &self.description.id,
+                    description.id,
+                    description.name,
+                    description.input.unwrap_or(Value::Null),
+                );
+                action.status = description.status;
+                action.time_requested = description.time_requested;
+                action.time_completed = description.time_completed;
+                self.actions.push(action.clone());
+                prune_completed_actions(&mut self.actions);
+                action
+            }
+        };
+        self.notify_action_status(action).await
+    }
+
+    async fn notify_action_status(&self, action: ActionRequest) -> Result<()> {
+        send!(
+            ThingsSocket.ThingsMessage(ThingsMessages::ActionStatusMessage(
+                ActionStatusMessage::new(self.description.id.clone(), action)
+            ))
+        )?;
+        Ok(())
+    }
+
+    /// Marks an action request as failed, e.g. because its add-on did not confirm it.
+    pub async fn fail_action(&mut self, action_id: &str) -> Result<()> {
+        let device_id = self.description.id.clone();
+        let action = self
+            .actions
+            .iter_mut()
+            .find(|action| action.id == action_id)
+            .ok_or_else(|| anyhow!("Device {} has no action request {}", device_id, action_id))?;
+        action.status = "failed".to_owned();
+        action.time_completed = Some(Utc::now().to_rfc3339());
+        let action = action.clone();
+        prune_completed_actions(&mut self.actions);
+        self.notify_action_status(action).await
+    }
+
+    pub fn remove_action(&mut self, action_id: &str) -> Option<ActionRequest> {
+        let index = self
+            .actions
+            .iter()
+            .position(|action| action.id == action_id)?;
+        Some(self.actions.remove(index))
+    }
 }
 
 impl Device {
@@ -62,6 +215,37 @@ impl Device {
         Self {
             description,
             connected: true,
+            actions: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prune_completed_actions() {
+        let mut actions: Vec<ActionRequest> = (0..2 * MAX_COMPLETED_ACTIONS + 10)
+            .map(|i| {
+                let mut action =
+                    ActionRequest::new("device", i.to_string(), "action".to_owned(), json!(null));
+                if i % 2 == 0 {
+                    action.status = "completed".to_owned();
+                }
+                action
+            })
+            .collect();
+        let pending = actions
+            .iter()
+            .filter(|action| !is_completed(action))
+            .count();
+
+        prune_completed_actions(&mut actions);
+        assert_eq!(actions.len(), pending + MAX_COMPLETED_ACTIONS);
+        assert_eq!(actions[0].id, "1");
+        assert!(!actions.iter().any(|action| action.id == "8"));
+        assert!(actions.iter().any(|action| action.id == "10"));
+    }
+}
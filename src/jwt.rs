@@ -45,7 +45,7 @@ impl JSONWebToken {
     }
 }
 
-async fn decode_token(token: &str) -> Result<TokenData<Claims>> {
+pub async fn decode_token(token: &str) -> Result<TokenData<Claims>> {
     let kid = jsonwebtoken::decode_header(token)?
         .kid
         .ok_or_else(|| anyhow!("Failed to obtain kid"))?;
@@ -59,11 +59,29 @@ async fn decode_token(token: &str) -> Result<TokenData<Claims>> {
 }
 
 fn extract_jwt_query(request: &Request<'_>) -> Option<String> {
-    if let Some(Ok(jwt)) = request.query_value("jwt") {
-        Some(jwt)
-    } else {
-        None
+    request
+        .uri()
+        .query()
+        .and_then(|query| parse_jwt_query(query.as_str()))
+}
+
+/// Extracts the token of an `Authorization: Bearer <token>` header value.
+pub fn parse_bearer(value: &str) -> Option<String> {
+    let mut jwt = value.split_whitespace();
+    if jwt.next() != Some("Bearer") {
+        return None;
     }
+    jwt.next().map(|token| token.to_string())
+}
+
+/// Extracts the `jwt` parameter of a raw query string.
+pub fn parse_jwt_query(query: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "jwt")
+        .and_then(|(_, value)| urlencoding::decode(value).ok())
+        .map(|value| value.into_owned())
 }
 
 fn extract_jwt_header(request: &Request<'_>) -> Option<String> {
@@ -71,11 +89,7 @@ fn extract_jwt_header(request: &Request<'_>) -> Option<String> {
     if keys.len() != 1 {
         return None;
     }
-    let mut jwt = keys[0].split_whitespace();
-    if jwt.next() != Some("Bearer") {
-        return None;
-    }
-    jwt.next().map(|token| token.to_string())
+    parse_bearer(keys[0])
 }
 
 fn extract_jwt(request: &Request<'_>) -> Option<String> {
@@ -146,6 +160,21 @@ mod tests {
     };
     use two_rusty_forks::test_fork;
 
+    #[test]
+    fn test_parse_bearer() {
+        assert_eq!(parse_bearer("Bearer abc"), Some("abc".to_owned()));
+        assert_eq!(parse_bearer("Basic abc"), None);
+        assert_eq!(parse_bearer("Bearer"), None);
+    }
+
+    #[test]
+    fn test_parse_jwt_query() {
+        assert_eq!(parse_jwt_query("jwt=abc"), Some("abc".to_owned()));
+        assert_eq!(parse_jwt_query("foo=bar&jwt=a%2Eb"), Some("a.b".to_owned()));
+        assert_eq!(parse_jwt_query("foo=bar"), None);
+        assert_eq!(parse_jwt_query(""), None);
+    }
+
     #[async_test]
     #[test_fork]
     async fn test_issue_decode() {
@@ -0,0 +1,493 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    macros::send,
+    things_socket::{LogSampleMessage, ThingsMessage, ThingsMessages, ThingsSocket},
+    user_config,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use chrono::Utc;
+use log::{debug, error};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{convert::TryFrom, ops::Deref, time::Duration};
+use xactor::{message, Actor, Context, Handler, Service};
+
+/// How often samples older than the retention of their log are removed.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A property whose changes are recorded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogDescription {
+    pub id: i64,
+    pub thing: String,
+    pub property: String,
+    /// Retention of the samples in milliseconds.
+    pub max_age: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Milliseconds since the Unix epoch.
+    pub time: i64,
+    pub value: Value,
+}
+
+/// Summary of the numeric samples in a bucket starting at `time`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bucket {
+    pub time: i64,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: usize,
+}
+
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Groups samples into buckets of `size` milliseconds aligned to `start`. Samples without a
+/// numeric value are skipped and empty buckets are left out.
+pub fn downsample(samples: &[Sample], start: i64, size: i64) -> Vec<Bucket> {
+    let mut buckets: Vec<Bucket> = Vec::new();
+    for sample in samples {
+        let value = match numeric(&sample.value) {
+            Some(value) => value,
+            None => continue,
+        };
+        let time = start + (sample.time - start).div_euclid(size) * size;
+        match buckets.last_mut() {
+            Some(bucket) if bucket.time == time => {
+                bucket.min = bucket.min.min(value);
+                bucket.max = bucket.max.max(value);
+                bucket.avg += (value - bucket.avg) / (bucket.count + 1) as f64;
+                bucket.count += 1;
+            }
+            _ => buckets.push(Bucket {
+                time,
+                min: value,
+                max: value,
+                avg: value,
+                count: 1,
+            }),
+        }
+    }
+    buckets
+}
+
+pub struct Logs(Connection);
+
+#[async_trait]
+impl Actor for Logs {
+    async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+        ctx.send_interval(Prune, PRUNE_INTERVAL);
+        Ok(())
+    }
+}
+
+impl Service for Logs {}
+
+impl Default for Logs {
+    fn default() -> Self {
+        let conn = Connection::open(user_config::DATA_DIR.join("logs.sqlite3"))
+            .expect("Open logs database file");
+        create_tables(&conn);
+        Self(conn)
+    }
+}
+
+impl Deref for Logs {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Logs {
+    fn find_log(&self, thing_id: &str, property: &str) -> Result<Option<i64>> {
+        self.query_row(
+            "SELECT id FROM metrics WHERE thingId = ? AND property = ?",
+            params![thing_id, property],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("Query metrics")
+    }
+}
+
+#[message(result = "Result<LogDescription>")]
+pub struct CreateLog(pub String, pub String, pub Duration);
+
+#[async_trait]
+impl Handler<CreateLog> for Logs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        CreateLog(thing, property, max_age): CreateLog,
+    ) -> Result<LogDescription> {
+        if self.find_log(&thing, &property)?.is_some() {
+            return Err(anyhow!(
+                "Property {} of thing {} is already logged",
+                property,
+                thing
+            ));
+        }
+        let max_age = i64::try_from(max_age.as_millis())
+            .map_err(|_| anyhow!("Retention of {:?} is too long", max_age))?;
+        self.execute(
+            "INSERT INTO metrics (thingId, property, maxAge) VALUES (?, ?, ?)",
+            params![thing, property, max_age],
+        )
+        .context("Insert into metrics")?;
+        Ok(LogDescription {
+            id: self.last_insert_rowid(),
+            thing,
+            property,
+            max_age,
+        })
+    }
+}
+
+#[message(result = "Result<Vec<LogDescription>>")]
+pub struct GetLogs;
+
+#[async_trait]
+impl Handler<GetLogs> for Logs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: GetLogs,
+    ) -> Result<Vec<LogDescription>> {
+        let mut stmt = self
+            .prepare("SELECT id, thingId, property, maxAge FROM metrics ORDER BY id")
+            .context("Prepare statement")?;
+        let mut rows = stmt.query([]).context("Execute query")?;
+        let mut logs = Vec::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            logs.push(LogDescription {
+                id: row.get(0).context("Get parameter")?,
+                thing: row.get(1).context("Get parameter")?,
+                property: row.get(2).context("Get parameter")?,
+                max_age: row.get(3).context("Get parameter")?,
+            });
+        }
+        Ok(logs)
+    }
+}
+
+#[message(result = "Result<()>")]
+pub struct DeleteLog(pub i64);
+
+#[async_trait]
+impl Handler<DeleteLog> for Logs {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, DeleteLog(id): DeleteLog) -> Result<()> {
+        let deleted = self
+            .execute("DELETE FROM metrics WHERE id = ?", params![id])
+            .context("Delete metric")?;
+        if deleted == 0 {
+            return Err(anyhow!("Log {} does not exist", id));
+        }
+        self.execute("DELETE FROM samples WHERE metricId = ?", params![id])
+            .context("Delete samples")?;
+        Ok(())
+    }
+}
+
+/// Removes the logs of all properties of a thing along with their samples.
+#[message(result = "Result<()>")]
+pub struct DeleteThingLogs(pub String);
+
+#[async_trait]
+impl Handler<DeleteThingLogs> for Logs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        DeleteThingLogs(thing): DeleteThingLogs,
+    ) -> Result<()> {
+        self.execute(
+            "DELETE FROM samples WHERE metricId IN (SELECT id FROM metrics WHERE thingId = ?)",
+            params![thing],
+        )
+        .context("Delete samples")?;
+        self.execute("DELETE FROM metrics WHERE thingId = ?", params![thing])
+            .context("Delete metrics")?;
+        Ok(())
+    }
+}
+
+/// Records a new value of a property if the property is logged.
+#[message(result = "Result<()>")]
+pub struct RecordSample(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<RecordSample> for Logs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        RecordSample(thing, property, value): RecordSample,
+    ) -> Result<()> {
+        let id = match self.find_log(&thing, &property)? {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let sample = Sample {
+            time: Utc::now().timestamp_millis(),
+            value,
+        };
+        let json = serde_json::to_string(&sample.value).context("Stringify sample")?;
+        self.execute(
+            "INSERT INTO samples (metricId, value, time) VALUES (?, ?, ?)",
+            params![id, json, sample.time],
+        )
+        .context("Insert into samples")?;
+        send!(ThingsSocket.ThingsMessage(ThingsMessages::LogSampleMessage(
+            LogSampleMessage::new(thing, property, sample)
+        )))?;
+        Ok(())
+    }
+}
+
+/// Returns the samples of a property recorded between two points in time, both inclusive.
+#[message(result = "Result<Option<Vec<Sample>>>")]
+pub struct GetSamples {
+    pub thing: String,
+    pub property: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+#[async_trait]
+impl Handler<GetSamples> for Logs {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetSamples,
+    ) -> Result<Option<Vec<Sample>>> {
+        let id = match self.find_log(&msg.thing, &msg.property)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let mut stmt = self
+            .prepare(
+                "SELECT time, value FROM samples
+                    WHERE metricId = ? AND time >= ? AND time <= ? ORDER BY time",
+            )
+            .context("Prepare statement")?;
+        let mut rows = stmt
+            .query(params![id, msg.start, msg.end])
+            .context("Execute query")?;
+        let mut samples = Vec::new();
+        while let Some(row) = rows.next().context("Next row")? {
+            let time: i64 = row.get(0).context("Get parameter")?;
+            let value: String = row.get(1).context("Get parameter")?;
+            samples.push(Sample {
+                time,
+                value: serde_json::from_str(&value).context("Parse JSON sample")?,
+            });
+        }
+        Ok(Some(samples))
+    }
+}
+
+#[message(result = "()")]
+#[derive(Clone)]
+struct Prune;
+
+#[async_trait]
+impl Handler<Prune> for Logs {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: Prune) {
+        let now = Utc::now().timestamp_millis();
+        match self.execute(
+            "DELETE FROM samples WHERE time < ? - (SELECT maxAge FROM metrics WHERE id = metricId)",
+            params![now],
+        ) {
+            Ok(pruned) => debug!("Pruned {} log samples", pruned),
+            Err(err) => error!("Failed to prune log samples: {}", err),
+        }
+    }
+}
+
+fn create_tables(conn: &Connection) {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS metrics(
+                  id INTEGER PRIMARY KEY ASC,
+                  thingId TEXT,
+                  property TEXT,
+                  maxAge INTEGER,
+                  UNIQUE (thingId, property)
+                  )",
+        [],
+    )
+    .expect("Create table metrics");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS samples(
+                  metricId INTEGER,
+                  value TEXT,
+                  time INTEGER
+                  )",
+        [],
+    )
+    .expect("Create table samples");
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS samplesByMetric ON samples(metricId, time)",
+        [],
+    )
+    .expect("Create index samplesByMetric");
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate two_rusty_forks;
+    use super::*;
+    use crate::{macros::call, tests_common::setup};
+    use serde_json::json;
+    use two_rusty_forks::test_fork;
+
+    fn sample(time: i64, value: Value) -> Sample {
+        Sample { time, value }
+    }
+
+    #[test]
+    fn test_downsample() {
+        let samples = vec![
+            sample(1000, json!(1)),
+            sample(1500, json!(3)),
+            sample(1999, json!("ignored")),
+            sample(3000, json!(true)),
+            sample(3100, json!(false)),
+        ];
+        assert_eq!(
+            downsample(&samples, 1000, 1000),
+            vec![
+                Bucket {
+                    time: 1000,
+                    min: 1.0,
+                    max: 3.0,
+                    avg: 2.0,
+                    count: 2
+                },
+                Bucket {
+                    time: 3000,
+                    min: 0.0,
+                    max: 1.0,
+                    avg: 0.5,
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_record_samples() {
+        let _ = setup();
+        let log = call!(Logs.CreateLog(
+            "thermometer".to_owned(),
+            "temperature".to_owned(),
+            Duration::from_secs(60)
+        ))
+        .unwrap();
+        assert!(call!(Logs.CreateLog(
+            "thermometer".to_owned(),
+            "temperature".to_owned(),
+            Duration::from_secs(60)
+        ))
+        .is_err());
+        assert_eq!(call!(Logs.GetLogs).unwrap(), vec![log.clone()]);
+
+        call!(Logs.RecordSample(
+            "thermometer".to_owned(),
+            "temperature".to_owned(),
+            json!(21.5)
+        ))
+        .unwrap();
+        call!(Logs.RecordSample("thermometer".to_owned(), "humidity".to_owned(), json!(40)))
+            .unwrap();
+
+        let samples = call!(Logs.GetSamples {
+            thing: "thermometer".to_owned(),
+            property: "temperature".to_owned(),
+            start: 0,
+            end: i64::MAX,
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].value, json!(21.5));
+        assert_eq!(
+            call!(Logs.GetSamples {
+                thing: "thermometer".to_owned(),
+                property: "humidity".to_owned(),
+                start: 0,
+                end: i64::MAX,
+            })
+            .unwrap(),
+            None
+        );
+
+        call!(Logs.DeleteLog(log.id)).unwrap();
+        assert!(call!(Logs.GetLogs).unwrap().is_empty());
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_reject_too_long_retention() {
+        let _ = setup();
+        assert!(call!(Logs.CreateLog(
+            "thermometer".to_owned(),
+            "temperature".to_owned(),
+            Duration::from_secs(u64::MAX)
+        ))
+        .is_err());
+        assert!(call!(Logs.GetLogs).unwrap().is_empty());
+    }
+
+    #[async_test]
+    #[test_fork]
+    async fn test_delete_thing_logs() {
+        let _ = setup();
+        for property in &["temperature", "humidity"] {
+            call!(Logs.CreateLog(
+                "thermometer".to_owned(),
+                property.to_string(),
+                Duration::from_secs(60)
+            ))
+            .unwrap();
+        }
+        let other =
+            call!(Logs.CreateLog("lamp".to_owned(), "on".to_owned(), Duration::from_secs(60)))
+                .unwrap();
+        call!(Logs.RecordSample(
+            "thermometer".to_owned(),
+            "temperature".to_owned(),
+            json!(21.5)
+        ))
+        .unwrap();
+
+        call!(Logs.DeleteThingLogs("thermometer".to_owned())).unwrap();
+        assert_eq!(call!(Logs.GetLogs).unwrap(), vec![other]);
+        assert_eq!(
+            call!(Logs.GetSamples {
+                thing: "thermometer".to_owned(),
+                property: "temperature".to_owned(),
+                start: 0,
+                end: i64::MAX,
+            })
+            .unwrap(),
+            None
+        );
+    }
+}
@@ -12,20 +12,29 @@ extern crate lazy_static;
 
 mod adapter;
 mod addon;
+mod addon_catalogue;
 mod addon_instance;
+mod addon_jobs;
 mod addon_manager;
 mod addon_socket;
 mod config;
 mod db;
 mod device;
 mod jwt;
+mod logs;
 mod macros;
 mod model;
+mod notifier;
+mod package;
 mod platform;
 mod process_manager;
 mod rest_api;
 mod reverse_proxy;
 mod router;
+mod rules;
+mod scenes;
+mod scheduler;
+mod schema;
 mod things_socket;
 mod user_config;
 
@@ -33,8 +42,10 @@ mod user_config;
 mod tests_common;
 
 use crate::{
+    addon_catalogue::{CheckForUpdates, UpdateChecker},
     addon_manager::{AddonManager, LoadAddons},
     macros::call,
+    scheduler::{ReloadSchedules, Scheduler},
 };
 use log::{info, LevelFilter};
 use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode};
@@ -73,6 +84,18 @@ async fn main() {
         }
     });
 
+    tokio::spawn(async {
+        if let Err(err) = call!(UpdateChecker.CheckForUpdates) {
+            error!("Failed to check for add-on updates: {:?}", err);
+        }
+    });
+
+    tokio::spawn(async {
+        if let Err(err) = call!(Scheduler.ReloadSchedules) {
+            error!("Failed to start scheduler: {:?}", err);
+        }
+    });
+
     tokio::spawn(async {
         if let Err(err) = reverse_proxy::start().await {
             error!("Failed to start reverse proxy {:?}", err);
@@ -4,15 +4,28 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
+use serde_json::Value;
+use std::{collections::BTreeMap, ops::Deref};
 use webthings_gateway_ipc_types::{Device, DeviceWithoutId};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Thing {
     #[serde(flatten)]
     pub device: Device,
     pub connected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_capability: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floorplan_x: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floorplan_y: Option<f64>,
+    /// Properties whose values were restored from the database because their add-on did not
+    /// report them, mapped to the time the value was last seen.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_properties: Option<BTreeMap<String, String>>,
 }
 
 impl Deref for Thing {
@@ -28,15 +41,25 @@ impl From<Thing> for ThingWithoutId {
         ThingWithoutId {
             device: thing.device.into_device_without_id(),
             connected: thing.connected,
+            selected_capability: thing.selected_capability,
+            floorplan_x: thing.floorplan_x,
+            floorplan_y: thing.floorplan_y,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct ThingWithoutId {
     #[serde(flatten)]
     pub device: DeviceWithoutId,
     pub connected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_capability: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floorplan_x: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floorplan_y: Option<f64>,
 }
 
 pub trait IntoThing {
@@ -48,6 +71,10 @@ impl IntoThing for ThingWithoutId {
         Thing {
             device: self.device.into_device(id),
             connected: self.connected,
+            selected_capability: self.selected_capability,
+            floorplan_x: self.floorplan_x,
+            floorplan_y: self.floorplan_y,
+            stale_properties: None,
         }
     }
 }
@@ -127,6 +154,194 @@ impl User {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionRequest {
+    #[serde(skip)]
+    pub id: String,
+    #[serde(skip)]
+    pub name: String,
+    pub input: Value,
+    pub href: String,
+    pub status: String,
+    pub time_requested: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_completed: Option<String>,
+}
+
+impl ActionRequest {
+    pub fn new(device_id: &str, id: String, name: String, input: Value) -> Self {
+        Self {
+            href: format!(
+                "/things/{}/actions/{}/{}",
+                urlencoding::encode(device_id),
+                urlencoding::encode(&name),
+                urlencoding::encode(&id)
+            ),
+            id,
+            name,
+            input,
+            status: "created".to_owned(),
+            time_requested: Utc::now().to_rfc3339(),
+            time_completed: None,
+        }
+    }
+}
+
+/// The last value an add-on reported for a property.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredPropertyValue {
+    pub value: Value,
+    pub timestamp: String,
+}
+
+impl StoredPropertyValue {
+    pub fn new(value: Value) -> Self {
+        Self {
+            value,
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThingEvent {
+    #[serde(skip)]
+    pub name: String,
+    pub data: Value,
+    pub timestamp: String,
+}
+
+/// Condition under which the effects of a rule are executed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Trigger {
+    PropertyEquals {
+        thing: String,
+        property: String,
+        value: Value,
+    },
+    PropertyAbove {
+        thing: String,
+        property: String,
+        threshold: f64,
+    },
+    PropertyBelow {
+        thing: String,
+        property: String,
+        threshold: f64,
+    },
+    Event {
+        thing: String,
+        event: String,
+    },
+    And {
+        triggers: Vec<Trigger>,
+    },
+    Or {
+        triggers: Vec<Trigger>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Effect {
+    SetProperty {
+        thing: String,
+        property: String,
+        value: Value,
+    },
+    RequestAction {
+        thing: String,
+        action: String,
+        #[serde(default)]
+        input: Value,
+    },
+    Notification {
+        title: String,
+        message: String,
+    },
+    /// Sends a notification through an outlet of a notifier add-on.
+    Notify {
+        notifier: String,
+        outlet: String,
+        title: String,
+        message: String,
+        #[serde(default = "crate::notifier::normal_level")]
+        level: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub id: i64,
+    #[serde(flatten)]
+    pub description: RuleWithoutId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RuleWithoutId {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    pub trigger: Trigger,
+    pub effects: Vec<Effect>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// When a schedule runs. Offsets of sunrise and sunset are given in minutes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Timing {
+    /// A cron expression with seconds, evaluated in the configured timezone.
+    Cron { expression: String },
+    /// A single point in time in RFC 3339 format.
+    Once { time: String },
+    Sunrise {
+        #[serde(default)]
+        offset: i64,
+    },
+    Sunset {
+        #[serde(default)]
+        offset: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub id: i64,
+    #[serde(flatten)]
+    pub description: ScheduleWithoutId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduleWithoutId {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    pub timing: Timing,
+    pub effects: Vec<Effect>,
+}
+
+/// Property values of several things, keyed by thing id and property name.
+pub type SceneValues = BTreeMap<String, BTreeMap<String, Value>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub id: i64,
+    #[serde(flatten)]
+    pub description: SceneWithoutId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneWithoutId {
+    pub name: String,
+    pub values: SceneValues,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Jwt {
     pub jwt: String,
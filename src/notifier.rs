@@ -0,0 +1,94 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    addon_manager::{AddonManager, Notify},
+    macros::call,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Urgency of a notification, from 0 (low) to 2 (high).
+pub const MAX_LEVEL: i64 = 2;
+
+pub fn normal_level() -> i64 {
+    1
+}
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotifierDescription {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutletDescription {
+    pub id: String,
+    pub name: String,
+}
+
+pub struct Notifier {
+    id: String,
+    name: String,
+    outlets: BTreeMap<String, OutletDescription>,
+}
+
+impl Notifier {
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            outlets: BTreeMap::new(),
+        }
+    }
+
+    pub fn description(&self) -> NotifierDescription {
+        NotifierDescription {
+            id: self.id.to_owned(),
+            name: self.name.to_owned(),
+        }
+    }
+
+    pub fn add_outlet(&mut self, outlet: OutletDescription) {
+        info!("Outlet {} of notifier {} added", outlet.id, self.id);
+        self.outlets.insert(outlet.id.to_owned(), outlet);
+    }
+
+    pub fn remove_outlet(&mut self, outlet_id: &str) {
+        if self.outlets.remove(outlet_id).is_some() {
+            info!("Outlet {} of notifier {} removed", outlet_id, self.id);
+        }
+    }
+
+    pub fn has_outlet(&self, outlet_id: &str) -> bool {
+        self.outlets.contains_key(outlet_id)
+    }
+
+    pub fn outlets(&self) -> Vec<OutletDescription> {
+        self.outlets.values().cloned().collect()
+    }
+}
+
+/// Sends a notification through an outlet and waits until the add-on confirms it.
+pub async fn notify(
+    notifier_id: String,
+    outlet_id: String,
+    title: String,
+    message: String,
+    level: i64,
+) -> Result<()> {
+    call!(AddonManager.Notify {
+        notifier_id,
+        outlet_id,
+        title,
+        message,
+        level,
+    })?
+    .wait(NOTIFY_TIMEOUT)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,362 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::addon::Extension;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use rust_manifest_types::Manifest;
+use std::{
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, EntryType};
+
+/// The directory all files of a package have to be in.
+pub const PACKAGE_ROOT: &str = "package";
+
+/// Upper bounds for the extracted content of a package.
+pub struct Limits {
+    pub max_file_size: u64,
+    pub max_total_size: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 200 * 1024 * 1024,
+            max_total_size: 500 * 1024 * 1024,
+        }
+    }
+}
+
+/// Normalizes the path of an archive entry and checks that it stays within the package root.
+fn entry_path(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => bail!("Invalid path {:?} in package", path),
+        }
+    }
+    if !normalized.starts_with(PACKAGE_ROOT) {
+        bail!("Path {:?} is outside of the package", path);
+    }
+    Ok(normalized)
+}
+
+/// Checks that a symbolic link at `path` pointing to `target` stays within the package root.
+fn check_symlink(path: &Path, target: &Path) -> Result<()> {
+    let mut resolved: Vec<_> = path
+        .parent()
+        .map(|parent| parent.components().collect())
+        .unwrap_or_default();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir if resolved.len() > 1 => {
+                resolved.pop();
+            }
+            _ => bail!("Link {:?} points outside of the package", path),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no directory leading to an entry is a link, which could redirect it elsewhere.
+fn check_parents(dest: &Path, path: &Path) -> Result<()> {
+    let mut parent = dest.to_owned();
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        parent.push(component);
+        if let Ok(metadata) = fs::symlink_metadata(&parent) {
+            if metadata.file_type().is_symlink() {
+                bail!("Path {:?} leads through a link", path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Extracts a `.tar.gz` package into `dest` and returns the path of its root directory.
+///
+/// Entries with absolute paths, `..` components or outside of the package root are rejected, as
+/// are links pointing outside of the package, special files and content exceeding the limits.
+pub fn extract(archive_path: &Path, dest: &Path, limits: &Limits) -> Result<PathBuf> {
+    let file = File::open(archive_path).context(format!("Failed to open {:?}", archive_path))?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+    let mut total_size = 0;
+    for entry in archive.entries().context("Failed to read package")? {
+        let mut entry = entry.context("Failed to read package entry")?;
+        let path = entry_path(&entry.path()?)?;
+        check_parents(dest, &path)?;
+        let out_path = dest.join(&path);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match entry.header().entry_type() {
+            EntryType::Directory => {
+                fs::create_dir_all(&out_path)?;
+            }
+            EntryType::Regular | EntryType::Continuous => {
+                let size = entry.size();
+                if size > limits.max_file_size {
+                    bail!("File {:?} exceeds {} bytes", path, limits.max_file_size);
+                }
+                total_size += size;
+                if total_size > limits.max_total_size {
+                    bail!("Package exceeds {} bytes", limits.max_total_size);
+                }
+                entry.unpack(&out_path)?;
+            }
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("Link {:?} has no target", path))?;
+                check_symlink(&path, &target)?;
+                entry.unpack(&out_path)?;
+            }
+            EntryType::Link => {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("Link {:?} has no target", path))?;
+                let target = entry_path(&target)?;
+                check_parents(dest, &target)?;
+                fs::hard_link(dest.join(target), &out_path)?;
+            }
+            EntryType::XGlobalHeader => {}
+            entry_type => bail!("Unsupported entry {:?} of type {:?}", path, entry_type),
+        }
+    }
+    let root = dest.join(PACKAGE_ROOT);
+    if !root.is_dir() {
+        bail!("Package has no {} directory", PACKAGE_ROOT);
+    }
+    Ok(root)
+}
+
+/// Checks that an extracted package contains a usable manifest and returns the add-on ID. If a
+/// specific add-on is expected, packages of any other add-on are rejected.
+pub fn validate(root: &Path, expected_id: Option<&str>) -> Result<String> {
+    let content = fs::read_to_string(root.join("manifest.json"))
+        .context(anyhow!("Package contains no manifest.json"))?;
+    let manifest: Manifest =
+        serde_json::from_str(&content).context(anyhow!("Could not read manifest.json"))?;
+    serde_json::from_str::<Extension>(&content)
+        .context(anyhow!("Could not read extensions from manifest.json"))?;
+    let id = manifest.id;
+    if id.is_empty() || id.starts_with('.') || id.contains(&['/', '\\'][..]) {
+        bail!("Invalid add-on id {:?}", id);
+    }
+    if let Some(expected_id) = expected_id {
+        if expected_id != id {
+            bail!("Package contains add-on {} instead of {}", id, expected_id);
+        }
+    }
+    let exec = &manifest.gateway_specific_settings.webthings.exec;
+    let program = exec
+        .split_ascii_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("Add-on {} has no exec command", id))?;
+    if let Some(file) = program.strip_prefix("{path}/") {
+        if !root.join(file).is_file() {
+            bail!("Executable {} of add-on {} is missing", file, id);
+        }
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+    use tar::{Builder, Header};
+    use tempdir::TempDir;
+
+    enum Entry<'a> {
+        File(&'a str, &'a [u8]),
+        Dir(&'a str),
+        Symlink(&'a str, &'a str),
+        HardLink(&'a str, &'a str),
+    }
+
+    /// Builds the header by hand, since `tar::Builder` refuses to write malicious paths.
+    fn header(path: &str, entry_type: EntryType, size: usize, link: &str) -> Header {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(size as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        header
+    }
+
+    fn archive(dir: &TempDir, entries: &[Entry]) -> PathBuf {
+        let path = dir.path().join("package.tar.gz");
+        let file = File::create(&path).unwrap();
+        let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+        for entry in entries {
+            let (header, data) = match entry {
+                Entry::File(path, data) => {
+                    (header(path, EntryType::Regular, data.len(), ""), *data)
+                }
+                Entry::Dir(path) => (header(path, EntryType::Directory, 0, ""), &b""[..]),
+                Entry::Symlink(path, target) => {
+                    (header(path, EntryType::Symlink, 0, target), &b""[..])
+                }
+                Entry::HardLink(path, target) => {
+                    (header(path, EntryType::Link, 0, target), &b""[..])
+                }
+            };
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn extract_entries(entries: &[Entry], limits: &Limits) -> (TempDir, Result<PathBuf>) {
+        let dir = TempDir::new("package").unwrap();
+        let path = archive(&dir, entries);
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        let result = extract(&path, &dest, limits);
+        (dir, result)
+    }
+
+    fn manifest(id: &str) -> Vec<u8> {
+        json!({
+            "manifest_version": 1,
+            "id": id,
+            "name": "Test Addon",
+            "short_name": "Test Addon",
+            "version": "1.0.0",
+            "description": "Test add-on",
+            "homepage_url": "https://example.com",
+            "license": "MPL-2.0",
+            "author": "WebThingsIO",
+            "gateway_specific_settings": {
+                "webthings": {
+                    "exec": "{path}/bin/addon {path}",
+                    "strict_min_version": "1.0.0",
+                    "strict_max_version": "*",
+                    "primary_type": "adapter"
+                }
+            }
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_extract_package() {
+        let manifest = manifest("test-addon");
+        let (dir, result) = extract_entries(
+            &[
+                Entry::Dir("package/"),
+                Entry::File("package/manifest.json", &manifest),
+                Entry::File("./package/bin/addon", b"#!/bin/sh"),
+                Entry::Symlink("package/bin/manifest.json", "../manifest.json"),
+                Entry::HardLink("package/addon", "package/bin/addon"),
+            ],
+            &Limits::default(),
+        );
+        let root = result.unwrap();
+        assert_eq!(root, dir.path().join("dest").join(PACKAGE_ROOT));
+        assert_eq!(fs::read(root.join("addon")).unwrap(), b"#!/bin/sh");
+        assert_eq!(fs::read(root.join("bin/manifest.json")).unwrap(), manifest);
+        assert_eq!(validate(&root, None).unwrap(), "test-addon");
+        assert_eq!(validate(&root, Some("test-addon")).unwrap(), "test-addon");
+        assert!(validate(&root, Some("other-addon")).is_err());
+    }
+
+    #[test]
+    fn test_reject_paths_outside_of_package() {
+        for path in &[
+            "package/../evil",
+            "../evil",
+            "/tmp/evil",
+            "evil",
+            "packages/evil",
+        ] {
+            let (dir, result) = extract_entries(
+                &[Entry::Dir("package/"), Entry::File(path, b"evil")],
+                &Limits::default(),
+            );
+            assert!(result.is_err(), "{} was extracted", path);
+            assert!(!dir.path().join("evil").exists());
+        }
+    }
+
+    #[test]
+    fn test_reject_links_outside_of_package() {
+        for target in &["../../evil", "../evil", "/tmp/evil", "a/../../../evil"] {
+            let (_dir, result) = extract_entries(
+                &[
+                    Entry::Dir("package/"),
+                    Entry::Symlink("package/link", target),
+                ],
+                &Limits::default(),
+            );
+            assert!(result.is_err(), "Link to {} was extracted", target);
+        }
+
+        let (_dir, result) = extract_entries(
+            &[
+                Entry::Dir("package/"),
+                Entry::Symlink("package/a", "."),
+                Entry::Symlink("package/a/b", "../evil"),
+            ],
+            &Limits::default(),
+        );
+        assert!(result.is_err());
+
+        let (dir, result) = extract_entries(
+            &[
+                Entry::Dir("package/"),
+                Entry::HardLink("package/link", "package/../package.tar.gz"),
+            ],
+            &Limits::default(),
+        );
+        assert!(result.is_err());
+        assert!(!dir.path().join("dest/package/link").exists());
+    }
+
+    #[test]
+    fn test_reject_oversized_content() {
+        let limits = Limits {
+            max_file_size: 8,
+            max_total_size: 12,
+        };
+        let (_dir, result) =
+            extract_entries(&[Entry::File("package/large", b"123456789")], &limits);
+        assert!(result.is_err());
+
+        let (_dir, result) = extract_entries(
+            &[
+                Entry::File("package/first", b"12345678"),
+                Entry::File("package/second", b"12345678"),
+            ],
+            &limits,
+        );
+        assert!(result.is_err());
+
+        let (_dir, result) = extract_entries(
+            &[
+                Entry::File("package/first", b"123456"),
+                Entry::File("package/second", b"123456"),
+            ],
+            &limits,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_reject_package_without_root() {
+        let (_dir, result) = extract_entries(&[], &Limits::default());
+        assert!(result.is_err());
+    }
+}
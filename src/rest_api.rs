@@ -5,11 +5,17 @@
 
 use crate::{config::CONFIG, router};
 use rocket::{
+    data::{ByteUnit, Limits, ToByteUnit},
     fs::{relative, FileServer},
-    Build, Rocket,
+    Build, Config, Rocket,
 };
 use std::env::{self, VarError};
 
+/// Largest add-on package which can be uploaded.
+fn package_limit() -> ByteUnit {
+    100.mebibytes()
+}
+
 fn rocket() -> Rocket<Build> {
     let ui_path = match env::var("WEBTHINGS_UI") {
         Ok(value) => value,
@@ -22,7 +28,11 @@ fn rocket() -> Rocket<Build> {
         }
     };
 
-    let rocket = rocket::build().mount("/", FileServer::from(ui_path));
+    let limits = Limits::default()
+        .limit("file", package_limit())
+        .limit("data-form", package_limit());
+    let figment = Config::figment().merge(("limits", limits));
+    let rocket = rocket::custom(figment).mount("/", FileServer::from(ui_path));
     router::mount(rocket)
 }
 
@@ -6,8 +6,13 @@
 pub(crate) mod addons_router;
 pub(crate) mod extensions_router;
 pub(crate) mod login_router;
+pub(crate) mod logs_router;
 pub(crate) mod new_things_router;
+pub(crate) mod notifiers_router;
 pub(crate) mod ping_router;
+pub(crate) mod rules_router;
+pub(crate) mod scenes_router;
+pub(crate) mod schedules_router;
 pub(crate) mod settings_router;
 pub(crate) mod things_router;
 pub(crate) mod users_router;
@@ -20,7 +25,12 @@ pub fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
         .mount("/addons/", addons_router::routes())
         .mount("/extensions/", extensions_router::routes())
         .mount("/login/", login_router::routes())
+        .mount("/logs/", logs_router::routes())
+        .mount("/notifiers/", notifiers_router::routes())
         .mount("/ping/", ping_router::routes())
+        .mount("/rules/", rules_router::routes())
+        .mount("/scenes/", scenes_router::routes())
+        .mount("/schedules/", schedules_router::routes())
         .mount("/settings/", settings_router::routes())
         .mount("/things/", things_router::routes())
         .mount("/users/", users_router::routes())
@@ -1,8 +1,10 @@
 use crate::{
     addon::Addon,
+    addon_catalogue::{self, AvailableAddon, GetPendingUpdates, UpdateChecker},
+    addon_jobs::{self, AddonJobs, GetJob, Job},
     addon_manager::{
         AddonManager, DisableAddon, EnableAddon, GetAddon, GetAddons, HasAddon,
-        InstallAddonFromUrl, RestartAddon, UninstallAddon,
+        InstallAddonFromFile, RestartAddon, RollbackAddon, UninstallAddon,
     },
     db::{Db, GetSetting, SetSetting},
     jwt::JSONWebToken,
@@ -11,24 +13,33 @@ use crate::{
 };
 use regex::Regex;
 use rocket::{
+    form::Form,
+    fs::TempFile,
     http::Status,
     response::status,
     serde::{json::Json, Deserialize, Serialize},
     Route,
 };
 use rust_manifest_types::Manifest;
-use std::{ffi::OsStr, fs, marker::PhantomData};
+use std::{ffi::OsStr, fs, marker::PhantomData, path::PathBuf};
+use tempdir::TempDir;
 
 pub fn routes() -> Vec<Route> {
     routes![
         get_addons,
+        get_available_addons,
+        get_pending_updates,
         put_addon,
         put_addon_config,
         get_addon_config,
         get_addon_license,
         delete_addon,
         post_addons,
+        upload_addon,
+        install_local_addon,
         patch_addon,
+        rollback_addon,
+        get_job,
     ]
 }
 
@@ -43,6 +54,26 @@ async fn get_addons(
     ))
 }
 
+#[get("/available")]
+async fn get_available_addons(
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<AvailableAddon>>, status::Custom<String>> {
+    let addons = addon_catalogue::available_addons().await.to_rocket(
+        "Failed to get available addons",
+        Status::InternalServerError,
+    )?;
+    Ok(Json(addons))
+}
+
+#[get("/updates")]
+async fn get_pending_updates(
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<AvailableAddon>>, status::Custom<String>> {
+    let updates = call!(UpdateChecker.GetPendingUpdates)
+        .to_rocket("Failed to get addon updates", Status::InternalServerError)?;
+    Ok(Json(updates))
+}
+
 #[derive(Serialize, Deserialize)]
 struct AddonEnabledState {
     enabled: bool,
@@ -196,17 +227,38 @@ struct InstallableAddon {
     checksum: String,
 }
 
+async fn start_install(
+    addon_id: String,
+    url: String,
+    checksum: String,
+    enable: bool,
+) -> Result<status::Accepted<Json<Job>>, status::Custom<String>> {
+    let job = addon_jobs::install_from_url(addon_id.to_owned(), url, checksum, enable)
+        .await
+        .to_rocket(
+            format!("Failed to install add-on {}", addon_id),
+            Status::BadRequest,
+        )?;
+    Ok(status::Accepted(Some(Json(job))))
+}
+
 #[post("/", data = "<data>")]
 async fn post_addons(
     data: Json<InstallableAddon>,
     _jwt: JSONWebToken,
-) -> Result<Json<AddonResponse>, status::Custom<String>> {
+) -> Result<status::Accepted<Json<Job>>, status::Custom<String>> {
     let inst = data.0;
-    let addon_id = inst.id.clone();
-    call!(AddonManager.InstallAddonFromUrl(inst.id, inst.url, inst.checksum, true)).to_rocket(
-        format!("Failed to install add-on {}", addon_id.clone()),
-        Status::InternalServerError,
-    )?;
+    start_install(inst.id, inst.url, inst.checksum, true).await
+}
+
+#[derive(FromForm)]
+struct Upload<'r> {
+    file: TempFile<'r>,
+}
+
+async fn install_from_file(path: PathBuf) -> Result<Json<AddonResponse>, status::Custom<String>> {
+    let addon_id = call!(AddonManager.InstallAddonFromFile(path, None, true))
+        .to_rocket("Failed to install add-on", Status::BadRequest)?;
     let addon = call!(AddonManager.GetAddon(addon_id.to_owned())).to_rocket(
         format!("Failed to get addon {}", addon_id),
         Status::InternalServerError,
@@ -214,6 +266,36 @@ async fn post_addons(
     Ok(Json(AddonResponse::from(addon)))
 }
 
+#[post("/upload", data = "<data>")]
+async fn upload_addon(
+    mut data: Form<Upload<'_>>,
+    _jwt: JSONWebToken,
+) -> Result<Json<AddonResponse>, status::Custom<String>> {
+    let temp_dir = TempDir::new("upload").to_rocket(
+        "Failed to create upload directory",
+        Status::InternalServerError,
+    )?;
+    let path = temp_dir.path().join("package.tar.gz");
+    data.file.persist_to(&path).await.to_rocket(
+        "Failed to store uploaded package",
+        Status::InternalServerError,
+    )?;
+    install_from_file(path).await
+}
+
+#[derive(Deserialize)]
+struct LocalPackage {
+    path: PathBuf,
+}
+
+#[post("/local", data = "<data>")]
+async fn install_local_addon(
+    data: Json<LocalPackage>,
+    _jwt: JSONWebToken,
+) -> Result<Json<AddonResponse>, status::Custom<String>> {
+    install_from_file(data.0.path).await
+}
+
 #[derive(Deserialize)]
 struct AddonOrigin {
     url: String,
@@ -225,16 +307,32 @@ async fn patch_addon(
     addon_id: String,
     data: Json<AddonOrigin>,
     _jwt: JSONWebToken,
-) -> Result<Json<AddonResponse>, status::Custom<String>> {
+) -> Result<status::Accepted<Json<Job>>, status::Custom<String>> {
     let inst = data.0;
-    call!(AddonManager.InstallAddonFromUrl(addon_id.clone(), inst.url, inst.checksum, false))
-        .to_rocket(
-            format!("Failed to update add-on {}", addon_id.clone()),
-            Status::InternalServerError,
-        )?;
+    start_install(addon_id, inst.url, inst.checksum, false).await
+}
+
+#[post("/<addon_id>/rollback")]
+async fn rollback_addon(
+    addon_id: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<AddonResponse>, status::Custom<String>> {
+    call!(AddonManager.RollbackAddon(addon_id.to_owned())).to_rocket(
+        format!("Failed to roll back add-on {}", addon_id),
+        Status::BadRequest,
+    )?;
     let addon = call!(AddonManager.GetAddon(addon_id.to_owned())).to_rocket(
         format!("Failed to get addon {}", addon_id),
         Status::InternalServerError,
     )?;
     Ok(Json(AddonResponse::from(addon)))
 }
+
+#[get("/jobs/<job_id>", rank = 1)]
+async fn get_job(job_id: u64, _jwt: JSONWebToken) -> Result<Json<Job>, status::Custom<String>> {
+    let job = call!(AddonJobs.GetJob(job_id)).to_rocket(
+        format!("Unable to find job with id = {}", job_id),
+        Status::NotFound,
+    )?;
+    Ok(Json(job))
+}
@@ -1,12 +1,173 @@
-use crate::jwt::JSONWebToken;
-use rocket::{serde::json::Json, Route};
-use std::collections::BTreeMap;
+use crate::{
+    addon::{Addon, ContentScript},
+    addon_manager::{AddonManager, ApiRequest, GetAddon, GetAddons},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+};
+use rocket::{
+    fs::NamedFile,
+    http::{ContentType, Status},
+    response::status,
+    serde::json::Json,
+    Route,
+};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::Duration,
+};
+
+const API_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub fn routes() -> Vec<Route> {
-    routes![get_extensions]
+    routes![
+        get_extensions,
+        get_resource,
+        get_api,
+        post_api,
+        put_api,
+        patch_api,
+        delete_api
+    ]
 }
 
 #[get("/")]
-fn get_extensions(_jwt: JSONWebToken) -> Json<BTreeMap<String, String>> {
-    Json(BTreeMap::new())
+async fn get_extensions(
+    _jwt: JSONWebToken,
+) -> Result<Json<BTreeMap<String, Vec<ContentScript>>>, status::Custom<String>> {
+    let addons = call!(AddonManager.GetAddons)
+        .to_rocket("Failed to get addons", Status::InternalServerError)?;
+    Ok(Json(
+        addons
+            .into_iter()
+            .filter(|(_, addon)| addon.enabled && !addon.extension.is_empty())
+            .map(|(id, addon)| (id, addon.extension.content_scripts))
+            .collect(),
+    ))
+}
+
+async fn get_enabled_addon(addon_id: &str) -> Result<Addon, status::Custom<String>> {
+    match call!(AddonManager.GetAddon(addon_id.to_owned())) {
+        Ok(addon) if addon.enabled => Ok(addon),
+        _ => Err(status::Custom(
+            Status::NotFound,
+            format!("Extension {} is not enabled", addon_id),
+        )),
+    }
+}
+
+#[get("/<addon_id>/<path..>", rank = 2)]
+async fn get_resource(
+    addon_id: String,
+    path: PathBuf,
+) -> Result<NamedFile, status::Custom<String>> {
+    let addon = get_enabled_addon(&addon_id).await?;
+    let resource = path.to_str().unwrap_or_default().replace('\\', "/");
+    if !addon.extension.is_accessible(&resource) {
+        return Err(status::Custom(
+            Status::NotFound,
+            format!("Extension {} has no resource {}", addon_id, resource),
+        ));
+    }
+    NamedFile::open(addon.path.join(path)).await.to_rocket(
+        format!("Failed to open resource {}", resource),
+        Status::NotFound,
+    )
+}
+
+async fn proxy(
+    addon_id: String,
+    method: &str,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    body: Option<Json<Value>>,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    get_enabled_addon(&addon_id).await?;
+    let body = match body.map(|body| body.0) {
+        Some(Value::Object(body)) => body.into_iter().collect(),
+        _ => BTreeMap::new(),
+    };
+    let request = call!(AddonManager.ApiRequest {
+        package_name: addon_id.to_owned(),
+        method: method.to_owned(),
+        path: format!("/{}", path.to_str().unwrap_or_default().replace('\\', "/")),
+        query: query
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect(),
+        body,
+    })
+    .to_rocket(
+        format!("Extension {} has no API handler", addon_id),
+        Status::NotFound,
+    )?;
+    let response = request.wait(API_TIMEOUT).await.map_err(|err| {
+        status::Custom(
+            err.status(),
+            format!("API handler {} failed: {}", addon_id, err),
+        )
+    })?;
+    let content_type = response
+        .content_type
+        .as_deref()
+        .and_then(ContentType::parse_flexible)
+        .unwrap_or(ContentType::Plain);
+    Ok((
+        Status::from_code(response.status).unwrap_or(Status::BadGateway),
+        (content_type, response.content.unwrap_or_default()),
+    ))
+}
+
+#[get("/<addon_id>/api/<path..>?<query..>")]
+async fn get_api(
+    addon_id: String,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    _jwt: JSONWebToken,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    proxy(addon_id, "GET", path, query, None).await
+}
+
+#[post("/<addon_id>/api/<path..>?<query..>", data = "<data>")]
+async fn post_api(
+    addon_id: String,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    data: Option<Json<Value>>,
+    _jwt: JSONWebToken,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    proxy(addon_id, "POST", path, query, data).await
+}
+
+#[put("/<addon_id>/api/<path..>?<query..>", data = "<data>")]
+async fn put_api(
+    addon_id: String,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    data: Option<Json<Value>>,
+    _jwt: JSONWebToken,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    proxy(addon_id, "PUT", path, query, data).await
+}
+
+#[patch("/<addon_id>/api/<path..>?<query..>", data = "<data>")]
+async fn patch_api(
+    addon_id: String,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    data: Option<Json<Value>>,
+    _jwt: JSONWebToken,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    proxy(addon_id, "PATCH", path, query, data).await
+}
+
+#[delete("/<addon_id>/api/<path..>?<query..>")]
+async fn delete_api(
+    addon_id: String,
+    path: PathBuf,
+    query: HashMap<String, String>,
+    _jwt: JSONWebToken,
+) -> Result<(Status, (ContentType, String)), status::Custom<String>> {
+    proxy(addon_id, "DELETE", path, query, None).await
 }
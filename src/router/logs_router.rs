@@ -0,0 +1,137 @@
+use crate::{
+    db::{Db, GetThing},
+    jwt::JSONWebToken,
+    logs::{self, Bucket, CreateLog, DeleteLog, GetLogs, GetSamples, LogDescription, Logs, Sample},
+    macros::{call, ToRocket},
+};
+use chrono::Utc;
+use rocket::{http::Status, response::status, serde::json::Json, Route};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, time::Duration};
+
+pub fn routes() -> Vec<Route> {
+    routes![get_logs, post_log, delete_log, get_samples]
+}
+
+/// Time range returned when a query does not specify a start, in milliseconds.
+const DEFAULT_RANGE: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewLog {
+    thing: String,
+    property: String,
+    /// Retention of the samples in milliseconds.
+    max_age: u64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Samples {
+    Raw(Vec<Sample>),
+    Downsampled(Vec<Bucket>),
+}
+
+#[get("/")]
+async fn get_logs(_jwt: JSONWebToken) -> Result<Json<Vec<LogDescription>>, status::Custom<String>> {
+    let logs = call!(Logs.GetLogs).to_rocket("Failed to get logs", Status::InternalServerError)?;
+    Ok(Json(logs))
+}
+
+#[post("/", data = "<data>")]
+async fn post_log(
+    data: Json<NewLog>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<LogDescription>>, status::Custom<String>> {
+    let NewLog {
+        thing,
+        property,
+        max_age,
+    } = data.0;
+    if i64::try_from(max_age).is_err() {
+        return Err(status::Custom(
+            Status::BadRequest,
+            format!("Retention of {} ms is too long", max_age),
+        ));
+    }
+    let stored = call!(Db.GetThing(thing.to_owned()))
+        .to_rocket("Error during db.get_thing", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::BadRequest,
+                format!("Unable to find thing with id = {}", thing),
+            )
+        })?;
+    if !stored
+        .properties
+        .as_ref()
+        .map(|properties| properties.contains_key(&property))
+        .unwrap_or(false)
+    {
+        return Err(status::Custom(
+            Status::BadRequest,
+            format!("Thing {} has no property {}", thing, property),
+        ));
+    }
+    let log = call!(Logs.CreateLog(thing, property, Duration::from_millis(max_age)))
+        .to_rocket("Failed to create log", Status::BadRequest)?;
+    info!(
+        "Logging property {} of thing {} as log {}",
+        log.property, log.thing, log.id
+    );
+    Ok(status::Created::new("").body(Json(log)))
+}
+
+#[delete("/<log_id>")]
+async fn delete_log(
+    log_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    call!(Logs.DeleteLog(log_id)).to_rocket("Failed to delete log", Status::NotFound)?;
+    Ok(status::NoContent)
+}
+
+#[get("/things/<thing_id>/properties/<property_name>?<start>&<end>&<bucket>")]
+async fn get_samples(
+    thing_id: String,
+    property_name: String,
+    start: Option<i64>,
+    end: Option<i64>,
+    bucket: Option<i64>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Samples>, status::Custom<String>> {
+    let end = end.unwrap_or_else(|| Utc::now().timestamp_millis());
+    let start = start.unwrap_or(end - DEFAULT_RANGE);
+    if start > end {
+        return Err(status::Custom(
+            Status::BadRequest,
+            "Start of range is after its end".to_owned(),
+        ));
+    }
+    let samples = call!(Logs.GetSamples {
+        thing: thing_id.to_owned(),
+        property: property_name.to_owned(),
+        start,
+        end,
+    })
+    .to_rocket("Failed to get samples", Status::InternalServerError)?
+    .ok_or_else(|| {
+        status::Custom(
+            Status::NotFound,
+            format!(
+                "Property {} of thing {} is not logged",
+                property_name, thing_id
+            ),
+        )
+    })?;
+    match bucket {
+        None => Ok(Json(Samples::Raw(samples))),
+        Some(size) if size > 0 => Ok(Json(Samples::Downsampled(logs::downsample(
+            &samples, start, size,
+        )))),
+        Some(_) => Err(status::Custom(
+            Status::BadRequest,
+            "Bucket size must be positive".to_owned(),
+        )),
+    }
+}
@@ -1,18 +1,21 @@
 use crate::{
-    addon_manager::{AddonManager, GetDevices},
+    addon_manager::{AddonManager, CancelPairing, GetDevices, StartPairing},
     db::{Db, GetThings},
     jwt::JSONWebToken,
     macros::{call, ToRocket},
 };
 use rocket::{http::Status, response::status, serde::json::Json, Route};
-use serde::{Serialize, Serializer};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{collections::BTreeMap, time::Duration};
 use webthings_gateway_ipc_types::{Device as DeviceDescription, Property as PropertyDescription};
 
 pub fn routes() -> Vec<Route> {
-    routes![get_new_things]
+    routes![get_new_things, start_pairing, cancel_pairing]
 }
 
+const DEFAULT_PAIRING_TIMEOUT: u64 = 60;
+const MAX_PAIRING_TIMEOUT: u64 = 600;
+
 fn serialize_device_description_without_properties<S>(
     device_description: &DeviceDescription,
     s: S,
@@ -86,7 +89,47 @@ async fn get_new_things(
     let new_devices = connected_devices
         .into_iter()
         .filter(|(id, _)| !stored_things.iter().any(|thing| &thing.id == id))
-        .map(|(_, device)| DeviceDescriptionWithHref::from(device))
+        .map(|(_, device)| DeviceDescriptionWithHref::from(device.description))
         .collect();
     Ok(Json(new_devices))
 }
+
+#[derive(Deserialize)]
+struct PairingRequest {
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct PairingSession {
+    id: u64,
+    timeout: u64,
+}
+
+#[post("/pairing", data = "<data>")]
+async fn start_pairing(
+    data: Option<Json<PairingRequest>>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<PairingSession>>, status::Custom<String>> {
+    let timeout = data
+        .and_then(|data| data.0.timeout)
+        .unwrap_or(DEFAULT_PAIRING_TIMEOUT);
+    if timeout == 0 || timeout > MAX_PAIRING_TIMEOUT {
+        return Err(status::Custom(
+            Status::BadRequest,
+            format!(
+                "Pairing timeout must be between 1 and {} seconds",
+                MAX_PAIRING_TIMEOUT
+            ),
+        ));
+    }
+    let id = call!(AddonManager.StartPairing(Duration::from_secs(timeout)))
+        .to_rocket("Failed to start pairing", Status::InternalServerError)?;
+    Ok(status::Created::new("").body(Json(PairingSession { id, timeout })))
+}
+
+#[delete("/pairing")]
+async fn cancel_pairing(_jwt: JSONWebToken) -> Result<status::NoContent, status::Custom<String>> {
+    call!(AddonManager.CancelPairing)
+        .to_rocket("Failed to cancel pairing", Status::InternalServerError)?;
+    Ok(status::NoContent)
+}
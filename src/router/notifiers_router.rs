@@ -0,0 +1,81 @@
+use crate::{
+    addon_manager::{AddonManager, GetNotifiers, GetOutlets},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+    notifier::{self, NotifierDescription, OutletDescription, MAX_LEVEL},
+};
+use rocket::{http::Status, response::status, serde::json::Json, Route};
+use serde::Deserialize;
+
+pub fn routes() -> Vec<Route> {
+    routes![get_notifiers, get_outlets, notify]
+}
+
+#[derive(Deserialize)]
+struct Notification {
+    title: String,
+    message: String,
+    #[serde(default = "notifier::normal_level")]
+    level: i64,
+}
+
+async fn get_notifier_outlets(
+    notifier_id: &str,
+) -> Result<Vec<OutletDescription>, status::Custom<String>> {
+    call!(AddonManager.GetOutlets(notifier_id.to_owned()))
+        .to_rocket("Failed to get outlets", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Unable to find notifier with id = {}", notifier_id),
+            )
+        })
+}
+
+#[get("/")]
+async fn get_notifiers(
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<NotifierDescription>>, status::Custom<String>> {
+    let notifiers = call!(AddonManager.GetNotifiers)
+        .to_rocket("Failed to get notifiers", Status::InternalServerError)?;
+    Ok(Json(notifiers))
+}
+
+#[get("/<notifier_id>/outlets")]
+async fn get_outlets(
+    notifier_id: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<OutletDescription>>, status::Custom<String>> {
+    Ok(Json(get_notifier_outlets(&notifier_id).await?))
+}
+
+#[post("/<notifier_id>/outlets/<outlet_id>/notify", data = "<data>")]
+async fn notify(
+    notifier_id: String,
+    outlet_id: String,
+    data: Json<Notification>,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    let outlets = get_notifier_outlets(&notifier_id).await?;
+    if !outlets.iter().any(|outlet| outlet.id == outlet_id) {
+        return Err(status::Custom(
+            Status::NotFound,
+            format!("Notifier {} has no outlet {}", notifier_id, outlet_id),
+        ));
+    }
+    let Notification {
+        title,
+        message,
+        level,
+    } = data.0;
+    if !(0..=MAX_LEVEL).contains(&level) {
+        return Err(status::Custom(
+            Status::BadRequest,
+            format!("Level must be between 0 and {}", MAX_LEVEL),
+        ));
+    }
+    notifier::notify(notifier_id, outlet_id, title, message, level)
+        .await
+        .to_rocket("Failed to send notification", Status::BadGateway)?;
+    Ok(status::NoContent)
+}
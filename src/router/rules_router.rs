@@ -0,0 +1,84 @@
+use crate::{
+    db::{CreateRule, Db, DeleteRule, GetRule, GetRules, UpdateRule},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+    model::{Rule, RuleWithoutId},
+    rules::{ReloadRules, RulesEngine},
+};
+use rocket::{http::Status, response::status, serde::json::Json, Route};
+
+pub fn routes() -> Vec<Route> {
+    routes![get_rules, get_rule, post_rule, put_rule, delete_rule]
+}
+
+async fn reload_rules() {
+    if let Err(err) = call!(RulesEngine.ReloadRules) {
+        error!("Failed to reload rules: {}", err);
+    }
+}
+
+async fn get_stored_rule(rule_id: i64) -> Result<Rule, status::Custom<String>> {
+    call!(Db.GetRule(rule_id))
+        .to_rocket("Error during db.get_rule", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Unable to find rule with id = {}", rule_id),
+            )
+        })
+}
+
+#[get("/")]
+async fn get_rules(_jwt: JSONWebToken) -> Result<Json<Vec<Rule>>, status::Custom<String>> {
+    let rules =
+        call!(Db.GetRules).to_rocket("Error during db.get_rules", Status::InternalServerError)?;
+    Ok(Json(rules))
+}
+
+#[get("/<rule_id>")]
+async fn get_rule(rule_id: i64, _jwt: JSONWebToken) -> Result<Json<Rule>, status::Custom<String>> {
+    Ok(Json(get_stored_rule(rule_id).await?))
+}
+
+#[post("/", data = "<data>")]
+async fn post_rule(
+    data: Json<RuleWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<Rule>>, status::Custom<String>> {
+    let rule = call!(Db.CreateRule(data.0))
+        .to_rocket("Error saving new rule", Status::InternalServerError)?;
+    info!("Successfully created new rule {}", rule.id);
+    reload_rules().await;
+    Ok(status::Created::new(format!("/rules/{}", rule.id)).body(Json(rule)))
+}
+
+#[put("/<rule_id>", data = "<data>")]
+async fn put_rule(
+    rule_id: i64,
+    data: Json<RuleWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Rule>, status::Custom<String>> {
+    get_stored_rule(rule_id).await?;
+    let rule = Rule {
+        id: rule_id,
+        description: data.0,
+    };
+    call!(Db.UpdateRule(rule.clone()))
+        .to_rocket("Failed to update rule", Status::InternalServerError)?;
+    info!("Successfully updated rule {}", rule_id);
+    reload_rules().await;
+    Ok(Json(rule))
+}
+
+#[delete("/<rule_id>")]
+async fn delete_rule(
+    rule_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    get_stored_rule(rule_id).await?;
+    call!(Db.DeleteRule(rule_id))
+        .to_rocket("Failed to delete rule", Status::InternalServerError)?;
+    info!("Successfully deleted rule {}", rule_id);
+    reload_rules().await;
+    Ok(status::NoContent)
+}
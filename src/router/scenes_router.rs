@@ -0,0 +1,111 @@
+use crate::{
+    db::{CreateScene, Db, DeleteScene, GetScene, GetScenes, UpdateScene},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+    model::{Scene, SceneWithoutId},
+    scenes::{self, PropertyResult},
+};
+use rocket::{http::Status, response::status, serde::json::Json, Route};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_scenes,
+        get_scene,
+        post_scene,
+        put_scene,
+        delete_scene,
+        apply_scene
+    ]
+}
+
+#[derive(Deserialize)]
+struct NewScene {
+    name: String,
+    /// Property names to capture per thing. An empty list captures all writable properties.
+    properties: BTreeMap<String, Vec<String>>,
+}
+
+async fn get_stored_scene(scene_id: i64) -> Result<Scene, status::Custom<String>> {
+    call!(Db.GetScene(scene_id))
+        .to_rocket("Error during db.get_scene", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Unable to find scene with id = {}", scene_id),
+            )
+        })
+}
+
+#[get("/")]
+async fn get_scenes(_jwt: JSONWebToken) -> Result<Json<Vec<Scene>>, status::Custom<String>> {
+    let scenes =
+        call!(Db.GetScenes).to_rocket("Error during db.get_scenes", Status::InternalServerError)?;
+    Ok(Json(scenes))
+}
+
+#[get("/<scene_id>")]
+async fn get_scene(
+    scene_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<Json<Scene>, status::Custom<String>> {
+    Ok(Json(get_stored_scene(scene_id).await?))
+}
+
+#[post("/", data = "<data>")]
+async fn post_scene(
+    data: Json<NewScene>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<Scene>>, status::Custom<String>> {
+    let NewScene { name, properties } = data.0;
+    let values = scenes::capture(properties)
+        .await
+        .to_rocket("Failed to capture scene", Status::BadRequest)?;
+    let scene = call!(Db.CreateScene(SceneWithoutId { name, values }))
+        .to_rocket("Error saving new scene", Status::InternalServerError)?;
+    info!("Successfully created new scene {}", scene.id);
+    Ok(status::Created::new(format!("/scenes/{}", scene.id)).body(Json(scene)))
+}
+
+#[put("/<scene_id>", data = "<data>")]
+async fn put_scene(
+    scene_id: i64,
+    data: Json<SceneWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Scene>, status::Custom<String>> {
+    get_stored_scene(scene_id).await?;
+    let scene = Scene {
+        id: scene_id,
+        description: data.0,
+    };
+    call!(Db.UpdateScene(scene.clone()))
+        .to_rocket("Failed to update scene", Status::InternalServerError)?;
+    info!("Successfully updated scene {}", scene_id);
+    Ok(Json(scene))
+}
+
+#[delete("/<scene_id>")]
+async fn delete_scene(
+    scene_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    get_stored_scene(scene_id).await?;
+    call!(Db.DeleteScene(scene_id))
+        .to_rocket("Failed to delete scene", Status::InternalServerError)?;
+    info!("Successfully deleted scene {}", scene_id);
+    Ok(status::NoContent)
+}
+
+#[post("/<scene_id>/apply")]
+async fn apply_scene(
+    scene_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<PropertyResult>>, status::Custom<String>> {
+    let scene = get_stored_scene(scene_id).await?;
+    let results = scenes::apply(scene.description.values)
+        .await
+        .to_rocket("Failed to apply scene", Status::InternalServerError)?;
+    info!("Applied scene {} ({})", scene_id, scene.description.name);
+    Ok(Json(results))
+}
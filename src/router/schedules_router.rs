@@ -0,0 +1,100 @@
+use crate::{
+    db::{CreateSchedule, Db, DeleteSchedule, GetSchedule, GetSchedules, UpdateSchedule},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+    model::{Schedule, ScheduleWithoutId},
+    scheduler::{self, ReloadSchedules, Scheduler},
+};
+use rocket::{http::Status, response::status, serde::json::Json, Route};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_schedules,
+        get_schedule,
+        post_schedule,
+        put_schedule,
+        delete_schedule
+    ]
+}
+
+pub(crate) async fn reload_schedules() {
+    if let Err(err) = call!(Scheduler.ReloadSchedules) {
+        error!("Failed to reload schedules: {}", err);
+    }
+}
+
+async fn get_stored_schedule(schedule_id: i64) -> Result<Schedule, status::Custom<String>> {
+    call!(Db.GetSchedule(schedule_id))
+        .to_rocket("Error during db.get_schedule", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Unable to find schedule with id = {}", schedule_id),
+            )
+        })
+}
+
+fn validate(description: &ScheduleWithoutId) -> Result<(), status::Custom<String>> {
+    scheduler::validate(&description.timing)
+        .map_err(|err| status::Custom(Status::BadRequest, format!("Invalid timing: {}", err)))
+}
+
+#[get("/")]
+async fn get_schedules(_jwt: JSONWebToken) -> Result<Json<Vec<Schedule>>, status::Custom<String>> {
+    let schedules = call!(Db.GetSchedules)
+        .to_rocket("Error during db.get_schedules", Status::InternalServerError)?;
+    Ok(Json(schedules))
+}
+
+#[get("/<schedule_id>")]
+async fn get_schedule(
+    schedule_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<Json<Schedule>, status::Custom<String>> {
+    Ok(Json(get_stored_schedule(schedule_id).await?))
+}
+
+#[post("/", data = "<data>")]
+async fn post_schedule(
+    data: Json<ScheduleWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<Schedule>>, status::Custom<String>> {
+    validate(&data)?;
+    let schedule = call!(Db.CreateSchedule(data.0))
+        .to_rocket("Error saving new schedule", Status::InternalServerError)?;
+    info!("Successfully created new schedule {}", schedule.id);
+    reload_schedules().await;
+    Ok(status::Created::new(format!("/schedules/{}", schedule.id)).body(Json(schedule)))
+}
+
+#[put("/<schedule_id>", data = "<data>")]
+async fn put_schedule(
+    schedule_id: i64,
+    data: Json<ScheduleWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Schedule>, status::Custom<String>> {
+    get_stored_schedule(schedule_id).await?;
+    validate(&data)?;
+    let schedule = Schedule {
+        id: schedule_id,
+        description: data.0,
+    };
+    call!(Db.UpdateSchedule(schedule.clone()))
+        .to_rocket("Failed to update schedule", Status::InternalServerError)?;
+    info!("Successfully updated schedule {}", schedule_id);
+    reload_schedules().await;
+    Ok(Json(schedule))
+}
+
+#[delete("/<schedule_id>")]
+async fn delete_schedule(
+    schedule_id: i64,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    get_stored_schedule(schedule_id).await?;
+    call!(Db.DeleteSchedule(schedule_id))
+        .to_rocket("Failed to delete schedule", Status::InternalServerError)?;
+    info!("Successfully deleted schedule {}", schedule_id);
+    reload_schedules().await;
+    Ok(status::NoContent)
+}
@@ -1,9 +1,26 @@
-use crate::{config::CONFIG, jwt::JSONWebToken, platform};
-use rocket::{response::status, serde::json::Json, Route};
+use crate::{
+    config::CONFIG,
+    db::{Db, SetSetting},
+    jwt::JSONWebToken,
+    macros::{call, ToRocket},
+    platform,
+    router::schedules_router::reload_schedules,
+    scheduler::{self, Location},
+};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use rocket::{http::Status, response::status, serde::json::Json, Route};
 use serde::{Deserialize, Serialize};
 
 pub fn routes() -> Vec<Route> {
-    routes![get_language, get_units, get_timezone, get_addons_info]
+    routes![
+        get_language,
+        get_units,
+        get_timezone,
+        put_timezone,
+        get_location,
+        put_location,
+        get_addons_info
+    ]
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -49,15 +66,65 @@ pub struct CurrentTimezone {
     pub valid: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NewTimezone {
+    pub zone: String,
+}
+
 #[get("/localization/timezone")]
-fn get_timezone(_jwt: JSONWebToken) -> Json<CurrentTimezone> {
+async fn get_timezone(_jwt: JSONWebToken) -> Json<CurrentTimezone> {
     Json(CurrentTimezone {
-        current: String::from("Europe/Berlin"),
+        current: scheduler::timezone().await.name().to_owned(),
         set_implemented: true,
-        valid: vec![String::from("Europe/Berlin")],
+        valid: TZ_VARIANTS.iter().map(|tz| tz.name().to_owned()).collect(),
     })
 }
 
+#[put("/localization/timezone", data = "<data>")]
+async fn put_timezone(
+    data: Json<NewTimezone>,
+    _jwt: JSONWebToken,
+) -> Result<Json<NewTimezone>, status::Custom<String>> {
+    let timezone: Tz = data.zone.parse().map_err(|_| {
+        status::Custom(
+            Status::BadRequest,
+            format!("Unknown timezone {}", data.zone),
+        )
+    })?;
+    call!(Db.SetSetting(
+        scheduler::TIMEZONE_KEY.to_owned(),
+        timezone.name().to_owned()
+    ))
+    .to_rocket("Failed to set timezone", Status::InternalServerError)?;
+    reload_schedules().await;
+    Ok(data)
+}
+
+#[get("/localization/location")]
+async fn get_location(_jwt: JSONWebToken) -> Result<Json<Location>, status::Custom<String>> {
+    scheduler::location()
+        .await
+        .map(Json)
+        .ok_or_else(|| status::Custom(Status::NotFound, "No location configured".to_owned()))
+}
+
+#[put("/localization/location", data = "<data>")]
+async fn put_location(
+    data: Json<Location>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Location>, status::Custom<String>> {
+    if !(-90.0..=90.0).contains(&data.latitude) || !(-180.0..=180.0).contains(&data.longitude) {
+        return Err(status::Custom(
+            Status::BadRequest,
+            "Latitude or longitude out of range".to_owned(),
+        ));
+    }
+    call!(Db.SetSetting(scheduler::LOCATION_KEY.to_owned(), data.0))
+        .to_rocket("Failed to set location", Status::InternalServerError)?;
+    reload_schedules().await;
+    Ok(data)
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct AddonsInfo {
@@ -1,20 +1,244 @@
 use crate::{
-    db::{CreateThing, Db, GetThing, GetThings},
+    addon_manager::{
+        confirm_action, AddonManager, GetActions, GetDevices, RemoveAction, RemoveDevice,
+        RequestAction, SetPropertyValue,
+    },
+    db::{
+        CreateThing, Db, DeleteThing, GetAllPropertyValues, GetEvents, GetPropertyValues, GetThing,
+        GetThings, UpdateThing,
+    },
+    device,
     jwt::JSONWebToken,
-    macros::{call, ToRocket},
-    model::Thing,
+    logs::{DeleteThingLogs, Logs},
+    macros::{call, send, ToRocket},
+    model::{ActionRequest, IntoDevice, StoredPropertyValue, Thing, ThingEvent},
+    schema,
+    things_socket::{
+        ThingAddedMessage, ThingModifiedMessage, ThingRemovedMessage, ThingsMessage,
+        ThingsMessages, ThingsSocket,
+    },
 };
-use rocket::{http::Status, response::status, serde::json::Json, Route};
-use webthings_gateway_ipc_types::Device;
+use rocket::{
+    http::Status,
+    response::{self, status, Responder},
+    serde::json::Json,
+    Request, Route,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+use webthings_gateway_ipc_types::{Device, DeviceWithoutId};
+
+const PROPERTY_TIMEOUT: Duration = Duration::from_secs(10);
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Top-level thing fields maintained by the gateway which a PATCH must not overwrite.
+const READ_ONLY_THING_FIELDS: [&str; 3] = ["id", "connected", "staleProperties"];
 
 pub fn routes() -> Vec<Route> {
-    routes![get_things, get_thing, post_things]
+    routes![
+        get_things,
+        get_thing,
+        post_things,
+        put_thing,
+        patch_thing,
+        delete_thing,
+        get_properties,
+        get_property,
+        put_property,
+        get_actions,
+        get_actions_by_name,
+        post_action,
+        delete_action,
+        get_events,
+        get_events_by_name
+    ]
+}
+
+fn group_actions(actions: Vec<ActionRequest>) -> Vec<BTreeMap<String, ActionRequest>> {
+    actions
+        .into_iter()
+        .map(|action| {
+            let mut entry = BTreeMap::new();
+            entry.insert(action.name.clone(), action);
+            entry
+        })
+        .collect()
+}
+
+fn group_events(events: Vec<ThingEvent>) -> Vec<BTreeMap<String, ThingEvent>> {
+    events
+        .into_iter()
+        .map(|event| {
+            let mut entry = BTreeMap::new();
+            entry.insert(event.name.clone(), event);
+            entry
+        })
+        .collect()
+}
+
+async fn get_stored_thing(thing_id: &str) -> Result<Thing, status::Custom<String>> {
+    call!(Db.GetThing(thing_id.to_owned()))
+        .to_rocket("Error during db.get_thing", Status::InternalServerError)?
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Unable to find thing with id = {}", thing_id),
+            )
+        })
+}
+
+async fn get_live_devices() -> Result<HashMap<String, device::Device>, status::Custom<String>> {
+    call!(AddonManager.GetDevices).to_rocket(
+        "Failed to get connected things",
+        Status::InternalServerError,
+    )
+}
+
+async fn get_live_device(thing_id: &str) -> Result<device::Device, status::Custom<String>> {
+    let mut devices = get_live_devices().await?;
+    devices.remove(thing_id).ok_or_else(|| {
+        status::Custom(
+            Status::NotFound,
+            format!("Thing {} is not connected", thing_id),
+        )
+    })
+}
+
+/// Property values, along with the names of those which were not reported by a running add-on
+/// and are served from the database instead. These are listed in a `Stale-Properties` header.
+struct PropertyValues<T> {
+    values: T,
+    stale: Vec<String>,
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for PropertyValues<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Json(self.values).respond_to(request)?;
+        if !self.stale.is_empty() {
+            response.set_raw_header("Stale-Properties", self.stale.join(","));
+        }
+        Ok(response)
+    }
+}
+
+async fn get_device(thing_id: &str) -> Result<Device, status::Custom<String>> {
+    Ok(get_live_device(thing_id).await?.description)
+}
+
+/// Overlays the stored description of a thing with the connection state and the property
+/// values of the matching device of a running add-on.
+///
+/// Properties the add-on has not reported are filled in from the last values stored in the
+/// database and listed as stale.
+fn merge_live_state(
+    thing: &mut Thing,
+    devices: &HashMap<String, device::Device>,
+    stored_values: &HashMap<String, StoredPropertyValue>,
+) {
+    let device = devices.get(&thing.id);
+    thing.connected = device.map(|device| device.connected()).unwrap_or(false);
+    let live_properties = device.and_then(|device| device.description.properties.as_ref());
+    let mut stale_properties = BTreeMap::new();
+    if let Some(properties) = thing.device.properties.as_mut() {
+        for (name, property) in properties.iter_mut() {
+            let live_value = live_properties
+                .and_then(|live_properties| live_properties.get(name))
+                .and_then(|live_property| live_property.value.clone());
+            if live_value.is_some() {
+                property.value = live_value;
+            } else if let Some(stored) = stored_values.get(name) {
+                property.value = Some(stored.value.clone());
+                stale_properties.insert(name.to_owned(), stored.timestamp.clone());
+            }
+        }
+    }
+    thing.stale_properties = if stale_properties.is_empty() {
+        None
+    } else {
+        Some(stale_properties)
+    };
+}
+
+async fn get_stored_values(
+    thing_id: &str,
+) -> Result<HashMap<String, StoredPropertyValue>, status::Custom<String>> {
+    call!(Db.GetPropertyValues(thing_id.to_owned())).to_rocket(
+        "Failed to get stored property values",
+        Status::InternalServerError,
+    )
+}
+
+/// Returns the property values of a thing. Values its add-on has not reported are taken from
+/// the database and listed as stale, just like in the thing description.
+async fn get_property_values(
+    thing_id: &str,
+) -> Result<PropertyValues<BTreeMap<String, Value>>, status::Custom<String>> {
+    let devices = get_live_devices().await?;
+    let stored = call!(Db.GetThing(thing_id.to_owned()))
+        .to_rocket("Error during db.get_thing", Status::InternalServerError)?;
+    let mut thing = match (stored, devices.get(thing_id)) {
+        (Some(thing), _) => thing,
+        (None, Some(device)) => Thing {
+            device: device.description.clone(),
+            connected: false,
+            selected_capability: None,
+            floorplan_x: None,
+            floorplan_y: None,
+            stale_properties: None,
+        },
+        (None, None) => {
+            return Err(status::Custom(
+                Status::NotFound,
+                format!("Unable to find thing with id = {}", thing_id),
+            ))
+        }
+    };
+    let stored_values = get_stored_values(thing_id).await?;
+    merge_live_state(&mut thing, &devices, &stored_values);
+    let values = thing
+        .device
+        .properties
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, property)| (name, property.value.unwrap_or(Value::Null)))
+        .collect();
+    let stale = thing
+        .stale_properties
+        .unwrap_or_default()
+        .into_keys()
+        .collect();
+    Ok(PropertyValues { values, stale })
+}
+
+async fn notify(msg: ThingsMessages) {
+    if let Err(err) = send!(ThingsSocket.ThingsMessage(msg)) {
+        warn!("Failed to notify things socket: {}", err);
+    }
+}
+
+async fn with_live_state(mut thing: Thing) -> Result<Thing, status::Custom<String>> {
+    let devices = get_live_devices().await?;
+    let stored_values = get_stored_values(&thing.id).await?;
+    merge_live_state(&mut thing, &devices, &stored_values);
+    Ok(thing)
 }
 
 #[get("/")]
 async fn get_things(_jwt: JSONWebToken) -> Result<Json<Vec<Thing>>, status::Custom<String>> {
-    let t =
+    let mut t =
         call!(Db.GetThings).to_rocket("Error during db.get_things", Status::InternalServerError)?;
+    let devices = get_live_devices().await?;
+    let stored_values = call!(Db.GetAllPropertyValues).to_rocket(
+        "Failed to get stored property values",
+        Status::InternalServerError,
+    )?;
+    for thing in t.iter_mut() {
+        let values = stored_values.get(&thing.id).cloned().unwrap_or_default();
+        merge_live_state(thing, &devices, &values);
+    }
 
     Ok(Json(t))
 }
@@ -27,7 +251,7 @@ async fn get_thing(
     let t = call!(Db.GetThing(thing_id.to_owned()))
         .to_rocket("Error during db.get_thing", Status::InternalServerError)?;
     if let Some(t) = t {
-        Ok(Some(Json(t)))
+        Ok(Some(Json(with_live_state(t).await?)))
     } else {
         Err(status::Custom(
             Status::NotFound,
@@ -56,6 +280,286 @@ async fn post_things(
             "Successfully created new thing {}",
             t.title.clone().unwrap_or_else(|| "".to_owned())
         );
+        let t = with_live_state(t).await?;
+        notify(ThingsMessages::ThingAddedMessage(ThingAddedMessage::new(
+            t.clone(),
+        )))
+        .await;
         Ok(status::Created::new("").body(Json(t)))
     }
 }
+
+async fn modified(thing_id: &str) -> Result<Json<Thing>, status::Custom<String>> {
+    let thing = with_live_state(get_stored_thing(thing_id).await?).await?;
+    notify(ThingsMessages::ThingModifiedMessage(
+        ThingModifiedMessage::new(thing.clone()),
+    ))
+    .await;
+    Ok(Json(thing))
+}
+
+#[put("/<thing_id>", data = "<data>")]
+async fn put_thing(
+    thing_id: String,
+    data: Json<DeviceWithoutId>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Thing>, status::Custom<String>> {
+    let mut thing = get_stored_thing(&thing_id).await?;
+    thing.device = data.0.into_device(thing_id.to_owned());
+    call!(Db.UpdateThing(thing))
+        .to_rocket("Failed to update thing", Status::InternalServerError)?;
+    info!("Successfully updated thing {}", thing_id);
+    modified(&thing_id).await
+}
+
+#[patch("/<thing_id>", data = "<data>")]
+async fn patch_thing(
+    thing_id: String,
+    data: Json<Value>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Thing>, status::Custom<String>> {
+    let thing = get_stored_thing(&thing_id).await?;
+    let changes = match data.0 {
+        Value::Object(changes) => changes,
+        _ => {
+            return Err(status::Custom(
+                Status::BadRequest,
+                "Expected an object with the changed fields".to_owned(),
+            ))
+        }
+    };
+    let mut value = serde_json::to_value(thing)
+        .to_rocket("Failed to serialize thing", Status::InternalServerError)?;
+    if let Some(fields) = value.as_object_mut() {
+        for (key, change) in changes {
+            if !READ_ONLY_THING_FIELDS.contains(&key.as_str()) {
+                fields.insert(key, change);
+            }
+        }
+    }
+    let thing: Thing =
+        serde_json::from_value(value).to_rocket("Invalid thing fields", Status::BadRequest)?;
+    call!(Db.UpdateThing(thing))
+        .to_rocket("Failed to update thing", Status::InternalServerError)?;
+    info!("Successfully patched thing {}", thing_id);
+    modified(&thing_id).await
+}
+
+#[delete("/<thing_id>")]
+async fn delete_thing(
+    thing_id: String,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    get_stored_thing(&thing_id).await?;
+    call!(Db.DeleteThing(thing_id.to_owned()))
+        .to_rocket("Failed to delete thing", Status::InternalServerError)?;
+    if let Err(err) = call!(AddonManager.RemoveDevice(thing_id.to_owned())) {
+        warn!(
+            "Could not remove device {} from its adapter: {}",
+            thing_id, err
+        );
+    }
+    if let Err(err) = call!(Logs.DeleteThingLogs(thing_id.to_owned())) {
+        warn!("Could not delete logs of thing {}: {}", thing_id, err);
+    }
+    info!("Successfully deleted thing {}", thing_id);
+    notify(ThingsMessages::ThingRemovedMessage(
+        ThingRemovedMessage::new(thing_id),
+    ))
+    .await;
+    Ok(status::NoContent)
+}
+
+#[get("/<thing_id>/properties")]
+async fn get_properties(
+    thing_id: String,
+    _jwt: JSONWebToken,
+) -> Result<PropertyValues<BTreeMap<String, Value>>, status::Custom<String>> {
+    get_property_values(&thing_id).await
+}
+
+#[get("/<thing_id>/properties/<property_name>")]
+async fn get_property(
+    thing_id: String,
+    property_name: String,
+    _jwt: JSONWebToken,
+) -> Result<PropertyValues<Value>, status::Custom<String>> {
+    let PropertyValues { mut values, stale } = get_property_values(&thing_id).await?;
+    let value = values.remove(&property_name).ok_or_else(|| {
+        status::Custom(
+            Status::NotFound,
+            format!("Thing {} has no property {}", thing_id, property_name),
+        )
+    })?;
+    Ok(PropertyValues {
+        values: value,
+        stale: stale
+            .into_iter()
+            .filter(|name| name == &property_name)
+            .collect(),
+    })
+}
+
+#[put("/<thing_id>/properties/<property_name>", data = "<data>")]
+async fn put_property(
+    thing_id: String,
+    property_name: String,
+    data: Json<Value>,
+    _jwt: JSONWebToken,
+) -> Result<Json<Value>, status::Custom<String>> {
+    let device = get_live_device(&thing_id).await?;
+    if !device
+        .description
+        .properties
+        .as_ref()
+        .map(|properties| properties.contains_key(&property_name))
+        .unwrap_or(false)
+    {
+        return Err(status::Custom(
+            Status::NotFound,
+            format!("Thing {} has no property {}", thing_id, property_name),
+        ));
+    }
+    device
+        .validate_property_value(&property_name, &data.0)
+        .to_rocket(
+            format!("Invalid value for property {}", property_name),
+            Status::BadRequest,
+        )?;
+    let request =
+        call!(AddonManager.SetPropertyValue(thing_id.to_owned(), property_name.to_owned(), data.0))
+            .to_rocket("Failed to set property", Status::InternalServerError)?;
+    let value = request.wait(PROPERTY_TIMEOUT).await.map_err(|err| {
+        status::Custom(
+            err.status(),
+            format!("Failed to set property {}: {}", property_name, err),
+        )
+    })?;
+    Ok(Json(value))
+}
+
+#[get("/<thing_id>/actions")]
+async fn get_actions(
+    thing_id: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<BTreeMap<String, ActionRequest>>>, status::Custom<String>> {
+    get_device(&thing_id).await?;
+    let actions = call!(AddonManager.GetActions(thing_id))
+        .to_rocket("Failed to get actions", Status::InternalServerError)?;
+    Ok(Json(group_actions(actions)))
+}
+
+#[get("/<thing_id>/actions/<action_name>")]
+async fn get_actions_by_name(
+    thing_id: String,
+    action_name: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<BTreeMap<String, ActionRequest>>>, status::Custom<String>> {
+    get_device(&thing_id).await?;
+    let actions = call!(AddonManager.GetActions(thing_id))
+        .to_rocket("Failed to get actions", Status::InternalServerError)?
+        .into_iter()
+        .filter(|action| action.name == action_name)
+        .collect();
+    Ok(Json(group_actions(actions)))
+}
+
+#[derive(Deserialize)]
+struct ActionInput {
+    #[serde(default)]
+    input: Value,
+}
+
+#[post("/<thing_id>/actions/<action_name>", data = "<data>")]
+async fn post_action(
+    thing_id: String,
+    action_name: String,
+    data: Json<BTreeMap<String, ActionInput>>,
+    _jwt: JSONWebToken,
+) -> Result<status::Created<Json<BTreeMap<String, ActionRequest>>>, status::Custom<String>> {
+    let device = get_device(&thing_id).await?;
+    let action = device
+        .actions
+        .and_then(|mut actions| actions.remove(&action_name))
+        .ok_or_else(|| {
+            status::Custom(
+                Status::NotFound,
+                format!("Thing {} has no action {}", thing_id, action_name),
+            )
+        })?;
+    let input = data
+        .0
+        .into_iter()
+        .find(|(name, _)| name == &action_name)
+        .map(|(_, action_input)| action_input.input)
+        .ok_or_else(|| {
+            status::Custom(
+                Status::BadRequest,
+                format!("Missing input for action {}", action_name),
+            )
+        })?;
+    schema::validate(&action.input.unwrap_or(Value::Null), &input)
+        .to_rocket("Invalid action input", Status::BadRequest)?;
+
+    let (action, request) =
+        call!(AddonManager.RequestAction(thing_id.to_owned(), action_name, input))
+            .to_rocket("Failed to request action", Status::InternalServerError)?;
+    confirm_action(&thing_id, &action, request, ACTION_TIMEOUT)
+        .await
+        .map_err(|err| {
+            status::Custom(err.status(), format!("Failed to request action: {}", err))
+        })?;
+    info!("Requested action {}", action.href);
+    let href = action.href.clone();
+    let mut response = BTreeMap::new();
+    response.insert(action.name.clone(), action);
+    Ok(status::Created::new(href).body(Json(response)))
+}
+
+#[delete("/<thing_id>/actions/<action_name>/<action_id>")]
+async fn delete_action(
+    thing_id: String,
+    action_name: String,
+    action_id: String,
+    _jwt: JSONWebToken,
+) -> Result<status::NoContent, status::Custom<String>> {
+    get_device(&thing_id).await?;
+    let known = call!(AddonManager.GetActions(thing_id.to_owned()))
+        .to_rocket("Failed to get actions", Status::InternalServerError)?
+        .iter()
+        .any(|action| action.id == action_id && action.name == action_name);
+    if !known {
+        return Err(status::Custom(
+            Status::NotFound,
+            format!("Unknown action request {}", action_id),
+        ));
+    }
+    let request = call!(AddonManager.RemoveAction(thing_id, action_name, action_id))
+        .to_rocket("Failed to remove action", Status::InternalServerError)?;
+    request
+        .wait(ACTION_TIMEOUT)
+        .await
+        .map_err(|err| status::Custom(err.status(), format!("Failed to remove action: {}", err)))?;
+    Ok(status::NoContent)
+}
+
+#[get("/<thing_id>/events")]
+async fn get_events(
+    thing_id: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<BTreeMap<String, ThingEvent>>>, status::Custom<String>> {
+    let events = call!(Db.GetEvents(thing_id, None))
+        .to_rocket("Failed to get events", Status::InternalServerError)?;
+    Ok(Json(group_events(events)))
+}
+
+#[get("/<thing_id>/events/<event_name>")]
+async fn get_events_by_name(
+    thing_id: String,
+    event_name: String,
+    _jwt: JSONWebToken,
+) -> Result<Json<Vec<BTreeMap<String, ThingEvent>>>, status::Custom<String>> {
+    let events = call!(Db.GetEvents(thing_id, Some(event_name)))
+        .to_rocket("Failed to get events", Status::InternalServerError)?;
+    Ok(Json(group_events(events)))
+}
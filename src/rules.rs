@@ -0,0 +1,370 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    addon_manager::{confirm_action, AddonManager, GetDevices, RequestAction, SetPropertyValue},
+    db::{Db, GetAllPropertyValues, GetRules},
+    macros::{call, send},
+    model::{Effect, Rule, Trigger},
+    notifier, schema,
+    things_socket::{NotificationMessage, ThingsMessage, ThingsMessages, ThingsSocket},
+};
+use anyhow::{anyhow, Result};
+use log::{debug, error, info};
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+use xactor::{message, Actor, Context, Handler, Service};
+
+const PROPERTY_TIMEOUT: Duration = Duration::from_secs(10);
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Last known values of all properties, keyed by thing and property id.
+type Values = HashMap<(String, String), Value>;
+
+fn property_value<'a>(values: &'a Values, thing: &str, property: &str) -> Option<&'a Value> {
+    values.get(&(thing.to_owned(), property.to_owned()))
+}
+
+/// Evaluates a trigger against the known property values. Event triggers only hold while the
+/// matching event is being handled.
+fn evaluate(trigger: &Trigger, values: &Values, event: Option<(&str, &str)>) -> bool {
+    match trigger {
+        Trigger::PropertyEquals {
+            thing,
+            property,
+            value,
+        } => property_value(values, thing, property) == Some(value),
+        Trigger::PropertyAbove {
+            thing,
+            property,
+            threshold,
+        } => property_value(values, thing, property)
+            .and_then(|value| value.as_f64())
+            .map(|value| value > *threshold)
+            .unwrap_or(false),
+        Trigger::PropertyBelow {
+            thing,
+            property,
+            threshold,
+        } => property_value(values, thing, property)
+            .and_then(|value| value.as_f64())
+            .map(|value| value < *threshold)
+            .unwrap_or(false),
+        Trigger::Event { thing, event: name } => event == Some((thing.as_str(), name.as_str())),
+        Trigger::And { triggers } => triggers
+            .iter()
+            .all(|trigger| evaluate(trigger, values, event)),
+        Trigger::Or { triggers } => triggers
+            .iter()
+            .any(|trigger| evaluate(trigger, values, event)),
+    }
+}
+
+fn watches_property(trigger: &Trigger, thing_id: &str, property_name: &str) -> bool {
+    match trigger {
+        Trigger::PropertyEquals {
+            thing, property, ..
+        }
+        | Trigger::PropertyAbove {
+            thing, property, ..
+        }
+        | Trigger::PropertyBelow {
+            thing, property, ..
+        } => thing == thing_id && property == property_name,
+        Trigger::Event { .. } => false,
+        Trigger::And { triggers } | Trigger::Or { triggers } => triggers
+            .iter()
+            .any(|trigger| watches_property(trigger, thing_id, property_name)),
+    }
+}
+
+fn watches_event(trigger: &Trigger, thing_id: &str, event_name: &str) -> bool {
+    match trigger {
+        Trigger::Event { thing, event } => thing == thing_id && event == event_name,
+        Trigger::And { triggers } | Trigger::Or { triggers } => triggers
+            .iter()
+            .any(|trigger| watches_event(trigger, thing_id, event_name)),
+        _ => false,
+    }
+}
+
+async fn apply(effect: Effect) -> Result<()> {
+    match effect {
+        Effect::SetProperty {
+            thing,
+            property,
+            value,
+        } => {
+            let devices = call!(AddonManager.GetDevices)?;
+            let device = devices
+                .get(&thing)
+                .ok_or_else(|| anyhow!("Thing {} is not connected", thing))?;
+            device.validate_property_value(&property, &value)?;
+            call!(AddonManager.SetPropertyValue(thing, property, value))?
+                .wait(PROPERTY_TIMEOUT)
+                .await?;
+        }
+        Effect::RequestAction {
+            thing,
+            action,
+            input,
+        } => {
+            let mut devices = call!(AddonManager.GetDevices)?;
+            let device = devices
+                .remove(&thing)
+                .ok_or_else(|| anyhow!("Thing {} is not connected", thing))?;
+            let description = device
+                .description
+                .actions
+                .and_then(|mut actions| actions.remove(&action))
+                .ok_or_else(|| anyhow!("Thing {} has no action {}", thing, action))?;
+            schema::validate(&description.input.unwrap_or(Value::Null), &input)?;
+            let (request_action, request) =
+                call!(AddonManager.RequestAction(thing.to_owned(), action, input))?;
+            confirm_action(&thing, &request_action, request, ACTION_TIMEOUT).await?;
+        }
+        Effect::Notification { title, message } => {
+            send!(
+                ThingsSocket.ThingsMessage(ThingsMessages::NotificationMessage(
+                    NotificationMessage::new(title, message)
+                ))
+            )?;
+        }
+        Effect::Notify {
+            notifier,
+            outlet,
+            title,
+            message,
+            level,
+        } => {
+            notifier::notify(notifier, outlet, title, message, level).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies effects in the background, one after the other, and waits for the add-ons to confirm
+/// them. Failing effects are logged with the name of their source.
+pub fn run_effects(source: String, effects: Vec<Effect>) {
+    tokio::spawn(async move {
+        for effect in effects {
+            if let Err(err) = apply(effect).await {
+                error!("Failed to apply effect of {}: {}", source, err);
+            }
+        }
+    });
+}
+
+fn execute(rule: &Rule) {
+    info!("Executing rule {} ({})", rule.id, rule.description.name);
+    run_effects(
+        format!("rule {}", rule.id),
+        rule.description.effects.clone(),
+    );
+}
+
+#[derive(Default)]
+pub struct RulesEngine {
+    rules: Vec<Rule>,
+    values: Values,
+    /// Whether the trigger of a rule held the last time it was evaluated, so that effects only
+    /// run when it starts to hold.
+    active: HashMap<i64, bool>,
+}
+
+impl RulesEngine {
+    /// Seeds the known values with the stored ones and those of the running add-ons, keeping
+    /// values which were reported since.
+    async fn load_values(&mut self) -> Result<()> {
+        let mut values = Values::new();
+        for (thing_id, stored_values) in call!(Db.GetAllPropertyValues)? {
+            for (name, stored) in stored_values {
+                values.insert((thing_id.to_owned(), name), stored.value);
+            }
+        }
+        for (thing_id, device) in call!(AddonManager.GetDevices)? {
+            for (name, property) in device.description.properties.unwrap_or_default() {
+                if let Some(value) = property.value {
+                    values.insert((thing_id.to_owned(), name), value);
+                }
+            }
+        }
+        values.extend(self.values.drain());
+        self.values = values;
+        Ok(())
+    }
+
+    async fn reload(&mut self) -> Result<()> {
+        self.load_values().await?;
+        self.rules = call!(Db.GetRules)?;
+        let values = &self.values;
+        self.active = self
+            .rules
+            .iter()
+            .map(|rule| (rule.id, evaluate(&rule.description.trigger, values, None)))
+            .collect();
+        debug!("Loaded {} rules", self.rules.len());
+        Ok(())
+    }
+
+    fn enabled_rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().filter(|rule| rule.description.enabled)
+    }
+}
+
+#[async_trait]
+impl Actor for RulesEngine {
+    async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+        // Loading asks the add-ons for their devices, which may be what started the engine.
+        ctx.address().send(ReloadRules)
+    }
+}
+
+impl Service for RulesEngine {}
+
+/// Reloads the rules after they were changed in the database.
+#[message(result = "Result<()>")]
+pub struct ReloadRules;
+
+#[async_trait]
+impl Handler<ReloadRules> for RulesEngine {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: ReloadRules) -> Result<()> {
+        self.reload().await
+    }
+}
+
+#[message(result = "()")]
+pub struct PropertyChanged(pub String, pub String, pub Value);
+
+#[async_trait]
+impl Handler<PropertyChanged> for RulesEngine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        PropertyChanged(thing_id, property_name, value): PropertyChanged,
+    ) {
+        self.values
+            .insert((thing_id.to_owned(), property_name.to_owned()), value);
+        let mut changes = Vec::new();
+        for rule in self.enabled_rules() {
+            if !watches_property(&rule.description.trigger, &thing_id, &property_name) {
+                continue;
+            }
+            let holds = evaluate(&rule.description.trigger, &self.values, None);
+            let held = self.active.get(&rule.id).copied().unwrap_or(false);
+            if holds && !held {
+                execute(rule);
+            }
+            if holds != held {
+                changes.push((rule.id, holds));
+            }
+        }
+        self.active.extend(changes);
+    }
+}
+
+#[message(result = "()")]
+pub struct EventFired(pub String, pub String);
+
+#[async_trait]
+impl Handler<EventFired> for RulesEngine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        EventFired(thing_id, event_name): EventFired,
+    ) {
+        let event = Some((thing_id.as_str(), event_name.as_str()));
+        for rule in self.enabled_rules() {
+            if watches_event(&rule.description.trigger, &thing_id, &event_name)
+                && evaluate(&rule.description.trigger, &self.values, event)
+            {
+                execute(rule);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn values(entries: &[(&str, &str, Value)]) -> Values {
+        entries
+            .iter()
+            .map(|(thing, property, value)| {
+                ((thing.to_string(), property.to_string()), value.clone())
+            })
+            .collect()
+    }
+
+    fn above(thing: &str, property: &str, threshold: f64) -> Trigger {
+        Trigger::PropertyAbove {
+            thing: thing.to_owned(),
+            property: property.to_owned(),
+            threshold,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_properties() {
+        let values = values(&[
+            ("switch", "on", json!(true)),
+            ("sensor", "temperature", json!(21.5)),
+        ]);
+        let equals = Trigger::PropertyEquals {
+            thing: "switch".to_owned(),
+            property: "on".to_owned(),
+            value: json!(true),
+        };
+        assert!(evaluate(&equals, &values, None));
+        assert!(evaluate(
+            &above("sensor", "temperature", 20.0),
+            &values,
+            None
+        ));
+        assert!(!evaluate(
+            &above("sensor", "temperature", 25.0),
+            &values,
+            None
+        ));
+        assert!(!evaluate(&above("sensor", "humidity", 0.0), &values, None));
+        let below = Trigger::PropertyBelow {
+            thing: "sensor".to_owned(),
+            property: "temperature".to_owned(),
+            threshold: 25.0,
+        };
+        assert!(evaluate(&below, &values, None));
+
+        let and = Trigger::And {
+            triggers: vec![equals.clone(), above("sensor", "temperature", 25.0)],
+        };
+        assert!(!evaluate(&and, &values, None));
+        let or = Trigger::Or {
+            triggers: vec![equals, above("sensor", "temperature", 25.0)],
+        };
+        assert!(evaluate(&or, &values, None));
+    }
+
+    #[test]
+    fn test_evaluate_events() {
+        let values = values(&[("sensor", "temperature", json!(30))]);
+        let event = Trigger::Event {
+            thing: "doorbell".to_owned(),
+            event: "ring".to_owned(),
+        };
+        assert!(!evaluate(&event, &values, None));
+        assert!(evaluate(&event, &values, Some(("doorbell", "ring"))));
+        assert!(!evaluate(&event, &values, Some(("doorbell", "knock"))));
+
+        let and = Trigger::And {
+            triggers: vec![event, above("sensor", "temperature", 25.0)],
+        };
+        assert!(evaluate(&and, &values, Some(("doorbell", "ring"))));
+        assert!(watches_event(&and, "doorbell", "ring"));
+        assert!(watches_property(&and, "sensor", "temperature"));
+        assert!(!watches_property(&and, "doorbell", "ring"));
+    }
+}
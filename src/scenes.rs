@@ -0,0 +1,106 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    addon_manager::{AddonManager, GetDevices, SetPropertyValue},
+    device::Device,
+    macros::call,
+    model::SceneValues,
+};
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+const PROPERTY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of writing a single property while applying a scene.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PropertyResult {
+    pub thing: String,
+    pub property: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reads the current values of the given properties of connected things. An empty list of
+/// properties captures all writable properties of a thing which have a value.
+pub async fn capture(properties: BTreeMap<String, Vec<String>>) -> Result<SceneValues> {
+    let devices = call!(AddonManager.GetDevices)?;
+    let mut values = SceneValues::new();
+    for (thing, names) in properties {
+        let described = devices
+            .get(&thing)
+            .ok_or_else(|| anyhow!("Thing {} is not connected", thing))?
+            .description
+            .properties
+            .clone()
+            .unwrap_or_default();
+        let names = if names.is_empty() {
+            described
+                .iter()
+                .filter(|(_, property)| {
+                    property.read_only != Some(true) && property.value.is_some()
+                })
+                .map(|(name, _)| name.to_owned())
+                .collect()
+        } else {
+            names
+        };
+        let captured = values.entry(thing.to_owned()).or_default();
+        for name in names {
+            let value = described
+                .get(&name)
+                .ok_or_else(|| anyhow!("Thing {} has no property {}", thing, name))?
+                .value
+                .clone()
+                .ok_or_else(|| anyhow!("Property {} of thing {} has no value", name, thing))?;
+            captured.insert(name, value);
+        }
+    }
+    Ok(values)
+}
+
+async fn set_property(
+    devices: &HashMap<String, Device>,
+    thing: &str,
+    property: &str,
+    value: Value,
+) -> Result<()> {
+    devices
+        .get(thing)
+        .ok_or_else(|| anyhow!("Thing {} is not connected", thing))?
+        .validate_property_value(property, &value)?;
+    call!(AddonManager.SetPropertyValue(thing.to_owned(), property.to_owned(), value))?
+        .wait(PROPERTY_TIMEOUT)
+        .await?;
+    Ok(())
+}
+
+/// Writes all values of a scene in parallel and reports the outcome per property.
+pub async fn apply(values: SceneValues) -> Result<Vec<PropertyResult>> {
+    let devices = call!(AddonManager.GetDevices)?;
+    let writes = values.into_iter().flat_map(|(thing, properties)| {
+        properties
+            .into_iter()
+            .map(move |(property, value)| (thing.to_owned(), property, value))
+    });
+    let devices = &devices;
+    Ok(join_all(writes.map(|(thing, property, value)| async move {
+        let result = set_property(devices, &thing, &property, value).await;
+        PropertyResult {
+            thing,
+            property,
+            success: result.is_ok(),
+            error: result.err().map(|err| err.to_string()),
+        }
+    }))
+    .await)
+}
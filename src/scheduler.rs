@@ -0,0 +1,332 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    db::{Db, GetSchedules, GetSetting, UpdateSchedule},
+    macros::call,
+    model::{Schedule, Timing},
+    rules,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, marker::PhantomData, str::FromStr, time::Duration};
+use xactor::{message, Actor, Context, Handler, Service};
+
+/// How often the scheduler checks for schedules which are due.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+pub const TIMEZONE_KEY: &str = "localization.timezone";
+pub const LOCATION_KEY: &str = "localization.location";
+
+/// Coordinates used to compute sunrise and sunset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl FromStr for Location {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+pub async fn timezone() -> Tz {
+    call!(Db.GetSetting::<String>(TIMEZONE_KEY.to_owned(), PhantomData))
+        .ok()
+        .and_then(|timezone| timezone.parse().ok())
+        .unwrap_or(DEFAULT_TIMEZONE)
+}
+
+pub async fn location() -> Option<Location> {
+    call!(Db.GetSetting::<Location>(LOCATION_KEY.to_owned(), PhantomData)).ok()
+}
+
+/// Unix timestamps of sunrise and sunset on the given date, following the sunrise equation.
+/// Returns `None` during polar day and polar night.
+fn sun_times(location: Location, date: NaiveDate) -> Option<(i64, i64)> {
+    let julian_day = date.and_hms(12, 0, 0).timestamp() as f64 / 86400.0 + 2_440_587.5;
+    let mean_solar_noon = julian_day - 2_451_545.0 + 0.0008 - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_solar_noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = 2_451_545.0 + mean_solar_noon + 0.0053 * anomaly.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+    let timestamp = |julian_day: f64| ((julian_day - 2_440_587.5) * 86400.0).round() as i64;
+    Some((
+        timestamp(transit - hour_angle),
+        timestamp(transit + hour_angle),
+    ))
+}
+
+fn next_sun_event(
+    after: DateTime<Utc>,
+    timezone: Tz,
+    location: Option<Location>,
+    offset: i64,
+    rise: bool,
+) -> Result<Option<DateTime<Utc>>> {
+    let location = location.ok_or_else(|| anyhow!("No location configured"))?;
+    let today = after.with_timezone(&timezone).date().naive_local();
+    for days in -1..=2 {
+        let date = today + ChronoDuration::days(days);
+        let (sunrise, sunset) = match sun_times(location, date) {
+            Some(times) => times,
+            None => continue,
+        };
+        let time =
+            Utc.timestamp(if rise { sunrise } else { sunset }, 0) + ChronoDuration::minutes(offset);
+        if time > after {
+            return Ok(Some(time));
+        }
+    }
+    Ok(None)
+}
+
+/// Computes when a schedule runs next after the given point in time, if ever.
+pub fn next_run(
+    timing: &Timing,
+    after: DateTime<Utc>,
+    timezone: Tz,
+    location: Option<Location>,
+) -> Result<Option<DateTime<Utc>>> {
+    match timing {
+        Timing::Cron { expression } => {
+            let schedule = cron::Schedule::from_str(expression)
+                .map_err(|err| anyhow!("Invalid cron expression {}: {}", expression, err))?;
+            Ok(schedule
+                .after(&after.with_timezone(&timezone))
+                .next()
+                .map(|time| time.with_timezone(&Utc)))
+        }
+        Timing::Once { time } => {
+            let time = DateTime::parse_from_rfc3339(time)
+                .map_err(|err| anyhow!("Invalid time {}: {}", time, err))?
+                .with_timezone(&Utc);
+            Ok(if time > after { Some(time) } else { None })
+        }
+        Timing::Sunrise { offset } => next_sun_event(after, timezone, location, *offset, true),
+        Timing::Sunset { offset } => next_sun_event(after, timezone, location, *offset, false),
+    }
+}
+
+/// Checks that a timing can be evaluated, independent of the configured location.
+pub fn validate(timing: &Timing) -> Result<()> {
+    match timing {
+        Timing::Sunrise { .. } | Timing::Sunset { .. } => Ok(()),
+        timing => next_run(timing, Utc::now(), DEFAULT_TIMEZONE, None).map(|_| ()),
+    }
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    schedules: HashMap<i64, Schedule>,
+    next_runs: HashMap<i64, DateTime<Utc>>,
+    timezone: Option<Tz>,
+    location: Option<Location>,
+}
+
+impl Scheduler {
+    fn plan(&mut self, schedule: &Schedule, after: DateTime<Utc>) {
+        let timezone = self.timezone.unwrap_or(DEFAULT_TIMEZONE);
+        match next_run(&schedule.description.timing, after, timezone, self.location) {
+            Ok(Some(time)) => {
+                debug!("Schedule {} runs next at {}", schedule.id, time);
+                self.next_runs.insert(schedule.id, time);
+            }
+            Ok(None) => {
+                self.next_runs.remove(&schedule.id);
+            }
+            Err(err) => {
+                error!("Failed to plan schedule {}: {}", schedule.id, err);
+                self.next_runs.remove(&schedule.id);
+            }
+        }
+    }
+
+    async fn reload(&mut self) -> Result<()> {
+        self.timezone = Some(timezone().await);
+        self.location = location().await;
+        self.schedules = call!(Db.GetSchedules)?
+            .into_iter()
+            .filter(|schedule| schedule.description.enabled)
+            .map(|schedule| (schedule.id, schedule))
+            .collect();
+        self.next_runs.clear();
+        let now = Utc::now();
+        for schedule in self.schedules.values().cloned().collect::<Vec<_>>() {
+            self.plan(&schedule, now);
+        }
+        debug!("Loaded {} schedules", self.schedules.len());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Actor for Scheduler {
+    async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+        ctx.send_interval(Tick, TICK_INTERVAL);
+        self.reload().await
+    }
+}
+
+impl Service for Scheduler {}
+
+/// Reloads the schedules after they or the localization settings were changed.
+#[message(result = "Result<()>")]
+pub struct ReloadSchedules;
+
+#[async_trait]
+impl Handler<ReloadSchedules> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: ReloadSchedules) -> Result<()> {
+        self.reload().await
+    }
+}
+
+#[message(result = "()")]
+#[derive(Clone)]
+struct Tick;
+
+#[async_trait]
+impl Handler<Tick> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: Tick) {
+        let now = Utc::now();
+        let due: Vec<(i64, DateTime<Utc>)> = self
+            .next_runs
+            .iter()
+            .filter(|(_, time)| **time <= now)
+            .map(|(id, time)| (*id, *time))
+            .collect();
+        for (id, time) in due {
+            let schedule = match self.schedules.get(&id) {
+                Some(schedule) => schedule.clone(),
+                None => continue,
+            };
+            info!("Running schedule {} ({})", id, schedule.description.name);
+            rules::run_effects(
+                format!("schedule {}", id),
+                schedule.description.effects.clone(),
+            );
+            if let Timing::Once { .. } = schedule.description.timing {
+                let mut schedule = schedule;
+                schedule.description.enabled = false;
+                if let Err(err) = call!(Db.UpdateSchedule(schedule)) {
+                    error!("Failed to disable schedule {}: {}", id, err);
+                }
+                self.schedules.remove(&id);
+                self.next_runs.remove(&id);
+            } else {
+                self.plan(&schedule, time.max(now));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America::New_York, UTC};
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_cron_run() {
+        let timing = Timing::Cron {
+            expression: "0 30 7 * * *".to_owned(),
+        };
+        assert_eq!(
+            next_run(&timing, utc("2021-06-01T12:00:00Z"), UTC, None).unwrap(),
+            Some(utc("2021-06-02T07:30:00Z"))
+        );
+        assert_eq!(
+            next_run(&timing, utc("2021-06-01T12:00:00Z"), New_York, None).unwrap(),
+            Some(utc("2021-06-01T11:30:00Z") + ChronoDuration::days(1))
+        );
+        assert!(validate(&Timing::Cron {
+            expression: "every morning".to_owned()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_next_once_run() {
+        let timing = Timing::Once {
+            time: "2021-06-01T18:00:00+02:00".to_owned(),
+        };
+        assert_eq!(
+            next_run(&timing, utc("2021-06-01T12:00:00Z"), UTC, None).unwrap(),
+            Some(utc("2021-06-01T16:00:00Z"))
+        );
+        assert_eq!(
+            next_run(&timing, utc("2021-06-01T17:00:00Z"), UTC, None).unwrap(),
+            None
+        );
+        assert!(validate(&Timing::Once {
+            time: "tomorrow".to_owned()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_next_sun_event() {
+        let berlin = Location {
+            latitude: 52.52,
+            longitude: 13.405,
+        };
+        let after = utc("2021-06-21T00:00:00Z");
+        let sunrise = next_run(
+            &Timing::Sunrise { offset: 0 },
+            after,
+            DEFAULT_TIMEZONE,
+            Some(berlin),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(sunrise.date(), after.date());
+        assert!(sunrise > utc("2021-06-21T02:30:00Z") && sunrise < utc("2021-06-21T03:00:00Z"));
+
+        let sunset = next_run(
+            &Timing::Sunset { offset: -30 },
+            after,
+            DEFAULT_TIMEZONE,
+            Some(berlin),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(sunset > utc("2021-06-21T18:45:00Z") && sunset < utc("2021-06-21T19:15:00Z"));
+
+        assert!(next_run(&Timing::Sunset { offset: 0 }, after, DEFAULT_TIMEZONE, None).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+fn type_matches(type_: &str, value: &Value) -> bool {
+    match type_ {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn validate_type(schema: &Map<String, Value>, value: &Value) -> Result<()> {
+    let matches = match schema.get("type") {
+        None => true,
+        Some(Value::String(type_)) => type_matches(type_, value),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|type_| type_.as_str())
+            .any(|type_| type_matches(type_, value)),
+        Some(type_) => bail!("Invalid type {} in schema", type_),
+    };
+    if !matches {
+        bail!("Value {} does not match type {}", value, schema["type"]);
+    }
+    Ok(())
+}
+
+fn validate_number(schema: &Map<String, Value>, value: f64) -> Result<()> {
+    if let Some(minimum) = schema.get("minimum").and_then(|minimum| minimum.as_f64()) {
+        if value < minimum {
+            bail!("Value {} is less than minimum {}", value, minimum);
+        }
+    }
+    if let Some(maximum) = schema.get("maximum").and_then(|maximum| maximum.as_f64()) {
+        if value > maximum {
+            bail!("Value {} is greater than maximum {}", value, maximum);
+        }
+    }
+    if let Some(multiple_of) = schema
+        .get("multipleOf")
+        .and_then(|multiple_of| multiple_of.as_f64())
+    {
+        let quotient = value / multiple_of;
+        if multiple_of > 0.0 && (quotient - quotient.round()).abs() > 1e-9 {
+            bail!("Value {} is not a multiple of {}", value, multiple_of);
+        }
+    }
+    Ok(())
+}
+
+fn validate_object(schema: &Map<String, Value>, object: &Map<String, Value>) -> Result<()> {
+    if let Some(required) = schema
+        .get("required")
+        .and_then(|required| required.as_array())
+    {
+        for key in required.iter().filter_map(|key| key.as_str()) {
+            if !object.contains_key(key) {
+                bail!("Missing required member {}", key);
+            }
+        }
+    }
+    if let Some(properties) = schema
+        .get("properties")
+        .and_then(|properties| properties.as_object())
+    {
+        for (key, member) in object {
+            if let Some(member_schema) = properties.get(key) {
+                validate(member_schema, member).map_err(|err| anyhow!("{}: {}", key, err))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validates a value against the subset of JSON schema used by WebThings descriptions.
+pub fn validate(schema: &Value, value: &Value) -> Result<()> {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        Value::Null => return Ok(()),
+        _ => bail!("Invalid schema {}", schema),
+    };
+
+    validate_type(schema, value)?;
+
+    if let Some(enum_) = schema.get("enum").and_then(|enum_| enum_.as_array()) {
+        if !enum_.is_empty() && !enum_.contains(value) {
+            bail!("Value {} is not one of {:?}", value, enum_);
+        }
+    }
+
+    match value {
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                validate_number(schema, number)?;
+            }
+        }
+        Value::Object(object) => validate_object(schema, object)?,
+        Value::Array(items) => {
+            if let Some(items_schema) = schema.get("items") {
+                for item in items {
+                    validate(items_schema, item)?;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Validates a value a client wants to write, refusing it altogether if the schema is marked as
+/// `readOnly`.
+pub fn validate_write(schema: &Value, value: &Value) -> Result<()> {
+    if schema
+        .get("readOnly")
+        .and_then(|read_only| read_only.as_bool())
+        == Some(true)
+    {
+        bail!("Value is read-only");
+    }
+    validate(schema, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_type() {
+        assert!(validate(&json!({"type": "integer"}), &json!(42)).is_ok());
+        assert!(validate(&json!({"type": "integer"}), &json!(4.2)).is_err());
+        assert!(validate(&json!({"type": "integer"}), &json!("42")).is_err());
+        assert!(validate(&json!({"type": "number"}), &json!(4.2)).is_ok());
+        assert!(validate(&json!({"type": "boolean"}), &json!(true)).is_ok());
+        assert!(validate(&json!({"type": "string"}), &json!(true)).is_err());
+        assert!(validate(&json!({"type": ["string", "null"]}), &json!(null)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let schema = json!({"type": "number", "minimum": 0, "maximum": 100, "multipleOf": 0.5});
+        assert!(validate(&schema, &json!(50.5)).is_ok());
+        assert!(validate(&schema, &json!(-1)).is_err());
+        assert!(validate(&schema, &json!(101)).is_err());
+        assert!(validate(&schema, &json!(50.2)).is_err());
+    }
+
+    #[test]
+    fn test_validate_enum() {
+        let schema = json!({"type": "string", "enum": ["on", "off"]});
+        assert!(validate(&schema, &json!("on")).is_ok());
+        assert!(validate(&schema, &json!("dim")).is_err());
+    }
+
+    #[test]
+    fn test_validate_object() {
+        let schema = json!({
+            "type": "object",
+            "required": ["level"],
+            "properties": {
+                "level": {"type": "integer", "minimum": 0, "maximum": 100},
+                "duration": {"type": "number"}
+            }
+        });
+        assert!(validate(&schema, &json!({"level": 50})).is_ok());
+        assert!(validate(&schema, &json!({"level": 50, "duration": 1.5})).is_ok());
+        assert!(validate(&schema, &json!({"duration": 1.5})).is_err());
+        assert!(validate(&schema, &json!({"level": 150})).is_err());
+        assert!(validate(&schema, &json!(50)).is_err());
+    }
+
+    #[test]
+    fn test_validate_without_schema() {
+        assert!(validate(&json!(null), &json!({"anything": true})).is_ok());
+        assert!(validate(&json!({}), &json!(42)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_write() {
+        let schema = json!({"type": "integer", "minimum": 0});
+        assert!(validate_write(&schema, &json!(1)).is_ok());
+        assert!(validate_write(&schema, &json!(-1)).is_err());
+        assert!(validate_write(&json!({"type": "integer", "readOnly": false}), &json!(1)).is_ok());
+        assert!(validate_write(&json!({"type": "integer", "readOnly": true}), &json!(1)).is_err());
+    }
+}
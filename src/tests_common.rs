@@ -1,6 +1,10 @@
 use std::env;
 
 use tempdir::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
 
 #[allow(unused_must_use)]
 pub fn setup() -> TempDir {
@@ -8,3 +12,28 @@ pub fn setup() -> TempDir {
     env::set_var("WEBTHINGS_HOME", dir.path());
     dir
 }
+
+/// Serves `body` to a single HTTP request and returns the URL of `path` on that server.
+pub async fn serve(path: &str, body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                return;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+    });
+    format!("http://{}{}", address, path)
+}
@@ -3,17 +3,52 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::{config::CONFIG, macros::call};
-use anyhow::Result;
+use crate::{
+    addon_instance::RequestError,
+    addon_jobs::Job,
+    addon_manager::{confirm_action, AddonManager, GetDevices, RequestAction, SetPropertyValue},
+    config::CONFIG,
+    db::{Db, GetThings},
+    jwt,
+    logs::Sample,
+    macros::call,
+    model::{ActionRequest, Thing, ThingEvent},
+    schema,
+};
+use anyhow::{anyhow, bail, Result};
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, net::SocketAddr};
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::{tungstenite, WebSocketStream};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, error::TrySendError},
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        self,
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        protocol::{frame::coding::CloseCode, CloseFrame},
+    },
+    WebSocketStream,
+};
+use webthings_gateway_ipc_types::Device as DeviceDescription;
 use xactor::{message, Actor, Context, Handler, Service};
 
+const PROPERTY_TIMEOUT: Duration = Duration::from_secs(10);
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HANDSHAKE_SIZE: usize = 16 * 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const OUTGOING_QUEUE_SIZE: usize = 64;
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct ConnectedMessage {
     id: String,
@@ -42,6 +77,164 @@ impl PropertyStatusMessage {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EventMessage {
+    id: String,
+    data: HashMap<String, ThingEvent>,
+}
+
+impl EventMessage {
+    pub fn new(id: String, event: ThingEvent) -> EventMessage {
+        let mut data = HashMap::new();
+        data.insert(event.name.clone(), event);
+
+        Self { id, data }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ActionStatusMessage {
+    id: String,
+    data: HashMap<String, ActionRequest>,
+}
+
+impl ActionStatusMessage {
+    pub fn new(id: String, action: ActionRequest) -> ActionStatusMessage {
+        let mut data = HashMap::new();
+        data.insert(action.name.clone(), action);
+
+        Self { id, data }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ThingAddedMessage {
+    id: String,
+    data: Thing,
+}
+
+impl ThingAddedMessage {
+    pub fn new(thing: Thing) -> ThingAddedMessage {
+        Self {
+            id: thing.id.clone(),
+            data: thing,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ThingModifiedMessage {
+    id: String,
+    data: Thing,
+}
+
+impl ThingModifiedMessage {
+    pub fn new(thing: Thing) -> ThingModifiedMessage {
+        Self {
+            id: thing.id.clone(),
+            data: thing,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ThingRemovedMessage {
+    id: String,
+}
+
+impl ThingRemovedMessage {
+    pub fn new(id: String) -> ThingRemovedMessage {
+        Self { id }
+    }
+}
+
+/// A device reported by an add-on which has not been saved as a thing yet.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ThingAvailableMessage {
+    id: String,
+    data: DeviceDescription,
+}
+
+impl ThingAvailableMessage {
+    pub fn new(device: DeviceDescription) -> ThingAvailableMessage {
+        Self {
+            id: device.id.clone(),
+            data: device,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LogSampleMessage {
+    id: String,
+    data: HashMap<String, Sample>,
+}
+
+impl LogSampleMessage {
+    pub fn new(id: String, property: String, sample: Sample) -> LogSampleMessage {
+        let mut data = HashMap::new();
+        data.insert(property, sample);
+
+        Self { id, data }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NotificationData {
+    title: String,
+    message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NotificationMessage {
+    data: NotificationData,
+}
+
+impl NotificationMessage {
+    pub fn new(title: String, message: String) -> NotificationMessage {
+        Self {
+            data: NotificationData { title, message },
+        }
+    }
+}
+
+/// Progress of an add-on installation.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AddonJobMessage {
+    data: Job,
+}
+
+impl AddonJobMessage {
+    pub fn new(job: Job) -> AddonJobMessage {
+        Self { data: job }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ErrorData {
+    status: String,
+    message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ErrorMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    data: ErrorData,
+}
+
+impl ErrorMessage {
+    pub fn new(id: Option<String>, status: StatusCode, message: String) -> ErrorMessage {
+        Self {
+            id,
+            data: ErrorData {
+                status: status.to_string(),
+                message,
+            },
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(tag = "messageType")]
 pub enum ThingsMessages {
@@ -49,6 +242,200 @@ pub enum ThingsMessages {
     ConnectedMessage(ConnectedMessage),
     #[serde(rename = "propertyStatus")]
     PropertyStatusMessage(PropertyStatusMessage),
+    #[serde(rename = "event")]
+    EventMessage(EventMessage),
+    #[serde(rename = "actionStatus")]
+    ActionStatusMessage(ActionStatusMessage),
+    #[serde(rename = "thingAdded")]
+    ThingAddedMessage(ThingAddedMessage),
+    #[serde(rename = "thingModified")]
+    ThingModifiedMessage(ThingModifiedMessage),
+    #[serde(rename = "thingRemoved")]
+    ThingRemovedMessage(ThingRemovedMessage),
+    #[serde(rename = "thingAvailable")]
+    ThingAvailableMessage(ThingAvailableMessage),
+    #[serde(rename = "logSample")]
+    LogSampleMessage(LogSampleMessage),
+    #[serde(rename = "notification")]
+    NotificationMessage(NotificationMessage),
+    #[serde(rename = "addonJob")]
+    AddonJobMessage(AddonJobMessage),
+    #[serde(rename = "error")]
+    ErrorMessage(ErrorMessage),
+}
+
+impl ThingsMessages {
+    fn thing_id(&self) -> Option<&str> {
+        match self {
+            ThingsMessages::ConnectedMessage(msg) => Some(&msg.id),
+            ThingsMessages::PropertyStatusMessage(msg) => Some(&msg.id),
+            ThingsMessages::EventMessage(msg) => Some(&msg.id),
+            ThingsMessages::ActionStatusMessage(msg) => Some(&msg.id),
+            ThingsMessages::ThingAddedMessage(msg) => Some(&msg.id),
+            ThingsMessages::ThingModifiedMessage(msg) => Some(&msg.id),
+            ThingsMessages::ThingRemovedMessage(msg) => Some(&msg.id),
+            ThingsMessages::ThingAvailableMessage(msg) => Some(&msg.id),
+            ThingsMessages::LogSampleMessage(msg) => Some(&msg.id),
+            ThingsMessages::NotificationMessage(_) => None,
+            ThingsMessages::AddonJobMessage(_) => None,
+            ThingsMessages::ErrorMessage(msg) => msg.id.as_deref(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ActionInput {
+    #[serde(default)]
+    input: Value,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "messageType")]
+enum IncomingMessages {
+    #[serde(rename = "setProperty")]
+    SetProperty {
+        id: Option<String>,
+        data: HashMap<String, Value>,
+    },
+    #[serde(rename = "requestAction")]
+    RequestAction {
+        id: Option<String>,
+        data: HashMap<String, ActionInput>,
+    },
+    #[serde(rename = "addEventSubscription")]
+    AddEventSubscription {
+        id: Option<String>,
+        data: HashMap<String, Value>,
+    },
+    #[serde(rename = "subscribe")]
+    Subscribe {
+        id: Option<String>,
+        data: Option<Vec<String>>,
+    },
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { id: Option<String> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Route {
+    Things,
+    Thing(String),
+    NewThings,
+    Logs,
+}
+
+fn parse_route(path: &str) -> Option<Route> {
+    let path = path.split('?').next().unwrap_or("").trim_end_matches('/');
+    let segments: Vec<_> = path.split('/').skip(1).collect();
+    match segments.as_slice() {
+        [] | ["things"] => Some(Route::Things),
+        ["new_things"] => Some(Route::NewThings),
+        ["logs"] => Some(Route::Logs),
+        ["things", id] if !id.is_empty() => urlencoding::decode(id)
+            .ok()
+            .map(|id| Route::Thing(id.into_owned())),
+        _ => None,
+    }
+}
+
+type Sink = SplitSink<WebSocketStream<TcpStream>, tokio_tungstenite::tungstenite::Message>;
+
+/// Things a subscriber narrowed its updates down to. A thing without property names receives
+/// all of its updates.
+type Subscriptions = HashMap<String, Option<HashSet<String>>>;
+
+struct Subscriber {
+    sender: mpsc::Sender<tungstenite::Message>,
+    route: Route,
+    event_subscriptions: HashSet<String>,
+    subscriptions: Option<Subscriptions>,
+}
+
+impl Subscriber {
+    fn new(sender: mpsc::Sender<tungstenite::Message>, route: Route) -> Self {
+        Self {
+            sender,
+            route,
+            event_subscriptions: HashSet::new(),
+            subscriptions: None,
+        }
+    }
+
+    fn subscribed(&self, msg: &ThingsMessages) -> bool {
+        let subscriptions = match &self.subscriptions {
+            None => return true,
+            Some(subscriptions) => subscriptions,
+        };
+        let properties = match msg.thing_id().and_then(|id| subscriptions.get(id)) {
+            None => return false,
+            Some(properties) => properties,
+        };
+        match (properties, msg) {
+            (Some(properties), ThingsMessages::PropertyStatusMessage(status)) => {
+                status.data.keys().any(|name| properties.contains(name))
+            }
+            (Some(properties), ThingsMessages::LogSampleMessage(sample)) => {
+                sample.data.keys().any(|name| properties.contains(name))
+            }
+            _ => true,
+        }
+    }
+
+    fn wants(&self, msg: &ThingsMessages) -> bool {
+        let routed = match (&self.route, msg) {
+            (Route::Logs, ThingsMessages::LogSampleMessage(_)) => true,
+            (Route::Logs, _) | (_, ThingsMessages::LogSampleMessage(_)) => false,
+            (Route::Things, _) => true,
+            (Route::NewThings, ThingsMessages::ThingAvailableMessage(_)) => true,
+            (Route::NewThings, _) => false,
+            (Route::Thing(thing_id), ThingsMessages::EventMessage(event)) => {
+                &event.id == thing_id
+                    && event
+                        .data
+                        .keys()
+                        .any(|name| self.event_subscriptions.contains(name))
+            }
+            (Route::Thing(thing_id), msg) => msg.thing_id() == Some(thing_id.as_str()),
+        };
+        routed && self.subscribed(msg)
+    }
+
+    /// Queues a message without waiting for the client. Returns `false` once the writer of the
+    /// client is gone or the client fell too far behind, and the subscriber should be dropped.
+    fn send(&self, msg: tungstenite::Message) -> bool {
+        match self.sender.try_send(msg) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                warn!("Things websocket client is too slow, disconnecting it");
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+fn to_text(msg: &ThingsMessages) -> Option<tungstenite::Message> {
+    match serde_json::to_string(msg) {
+        Ok(json) => Some(tungstenite::Message::Text(json)),
+        Err(err) => {
+            error!("Failed to serialize message: {}", err);
+            None
+        }
+    }
+}
+
+/// Forwards the queued messages of a client to its websocket until either side goes away. Once
+/// the subscriber is dropped, the websocket is closed so that the client reconnects.
+async fn write_messages(mut sink: Sink, mut receiver: mpsc::Receiver<tungstenite::Message>) {
+    while let Some(msg) = receiver.recv().await {
+        if let Err(err) = sink.send(msg).await {
+            debug!("Failed to send things message: {:?}", err);
+            return;
+        }
+    }
+    if let Err(err) = sink.close().await {
+        debug!("Failed to close things websocket: {:?}", err);
+    }
 }
 
 #[message(result = "()")]
@@ -57,57 +444,537 @@ pub struct ThingsMessage(pub ThingsMessages);
 #[async_trait]
 impl Handler<ThingsMessage> for ThingsSocket {
     async fn handle(&mut self, _: &mut Context<Self>, ThingsMessage(msg): ThingsMessage) {
-        for sink in &mut self.sinks {
-            match serde_json::to_string(&msg) {
-                Ok(json) => {
-                    if let Err(err) = sink.send(tungstenite::Message::Text(json)).await {
-                        error!("Failed to send things message: {:?}", err);
-                    }
-                }
-                Err(err) => {
-                    error!("Failed to serialize message: {}", err)
-                }
+        let text = match to_text(&msg) {
+            Some(text) => text,
+            None => return,
+        };
+        self.subscribers.retain(|id, subscriber| {
+            if !subscriber.wants(&msg) || subscriber.send(text.clone()) {
+                return true;
             }
-        }
+            debug!("Dropping things websocket subscriber {}", id);
+            false
+        });
     }
 }
 
-#[message(result = "Result<()>")]
-struct RegisterSink(SplitSink<WebSocketStream<TcpStream>, tokio_tungstenite::tungstenite::Message>);
+#[message(result = "Result<u64>")]
+struct RegisterSink(mpsc::Sender<tungstenite::Message>, Route);
 
 #[async_trait]
 impl Handler<RegisterSink> for ThingsSocket {
     async fn handle(
         &mut self,
         _: &mut Context<Self>,
-        RegisterSink(sink): RegisterSink,
-    ) -> Result<()> {
-        self.sinks.push(sink);
+        RegisterSink(sender, route): RegisterSink,
+    ) -> Result<u64> {
+        self.next_id += 1;
+        self.subscribers
+            .insert(self.next_id, Subscriber::new(sender, route));
+
+        Ok(self.next_id)
+    }
+}
+
+#[message(result = "()")]
+struct UnregisterSink(u64);
 
-        Ok(())
+#[async_trait]
+impl Handler<UnregisterSink> for ThingsSocket {
+    async fn handle(&mut self, _: &mut Context<Self>, UnregisterSink(id): UnregisterSink) {
+        self.subscribers.remove(&id);
+    }
+}
+
+#[message(result = "()")]
+struct CloseSink(u64, String);
+
+#[async_trait]
+impl Handler<CloseSink> for ThingsSocket {
+    async fn handle(&mut self, _: &mut Context<Self>, CloseSink(id, reason): CloseSink) {
+        if let Some(subscriber) = self.subscribers.remove(&id) {
+            let frame = CloseFrame {
+                code: CloseCode::Policy,
+                reason: reason.into(),
+            };
+            subscriber.send(tungstenite::Message::Close(Some(frame)));
+        }
+    }
+}
+
+#[message(result = "()")]
+struct SendTo(u64, ThingsMessages);
+
+#[async_trait]
+impl Handler<SendTo> for ThingsSocket {
+    async fn handle(&mut self, _: &mut Context<Self>, SendTo(id, msg): SendTo) {
+        if let (Some(subscriber), Some(text)) = (self.subscribers.get(&id), to_text(&msg)) {
+            if !subscriber.send(text) {
+                self.subscribers.remove(&id);
+            }
+        }
+    }
+}
+
+#[message(result = "()")]
+struct AddEventSubscription(u64, String);
+
+#[async_trait]
+impl Handler<AddEventSubscription> for ThingsSocket {
+    async fn handle(
+        &mut self,
+        _: &mut Context<Self>,
+        AddEventSubscription(id, event_name): AddEventSubscription,
+    ) {
+        if let Some(subscriber) = self.subscribers.get_mut(&id) {
+            subscriber.event_subscriptions.insert(event_name);
+        }
+    }
+}
+
+#[message(result = "()")]
+struct Subscribe(u64, String, Option<Vec<String>>);
+
+#[async_trait]
+impl Handler<Subscribe> for ThingsSocket {
+    async fn handle(
+        &mut self,
+        _: &mut Context<Self>,
+        Subscribe(id, thing_id, properties): Subscribe,
+    ) {
+        if let Some(subscriber) = self.subscribers.get_mut(&id) {
+            let subscriptions = subscriber.subscriptions.get_or_insert_with(HashMap::new);
+            match properties {
+                None => {
+                    subscriptions.insert(thing_id, None);
+                }
+                Some(properties) => {
+                    if let Some(subscribed) = subscriptions
+                        .entry(thing_id)
+                        .or_insert_with(|| Some(HashSet::new()))
+                    {
+                        subscribed.extend(properties);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[message(result = "()")]
+struct Unsubscribe(u64, String);
+
+#[async_trait]
+impl Handler<Unsubscribe> for ThingsSocket {
+    async fn handle(&mut self, _: &mut Context<Self>, Unsubscribe(id, thing_id): Unsubscribe) {
+        if let Some(subscriptions) = self
+            .subscribers
+            .get_mut(&id)
+            .and_then(|subscriber| subscriber.subscriptions.as_mut())
+        {
+            subscriptions.remove(&thing_id);
+        }
     }
 }
 
 #[derive(Default)]
 pub struct ThingsSocket {
-    sinks: Vec<SplitSink<WebSocketStream<TcpStream>, tokio_tungstenite::tungstenite::Message>>,
+    subscribers: HashMap<u64, Subscriber>,
+    next_id: u64,
 }
 
 impl Actor for ThingsSocket {}
 
 impl Service for ThingsSocket {}
 
+/// Announces the devices which are already waiting to be saved to a new `/new_things` client.
+async fn send_new_things(subscriber_id: u64) -> Result<()> {
+    let stored_things = call!(Db.GetThings)?;
+    let devices = call!(AddonManager.GetDevices)?;
+    for (id, device) in devices {
+        if !stored_things.iter().any(|thing| thing.id == id) {
+            call!(ThingsSocket.SendTo(
+                subscriber_id,
+                ThingsMessages::ThingAvailableMessage(ThingAvailableMessage::new(
+                    device.description
+                ))
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+async fn reply(subscriber_id: u64, msg: ErrorMessage) {
+    if let Err(err) = call!(ThingsSocket.SendTo(subscriber_id, ThingsMessages::ErrorMessage(msg))) {
+        error!("Failed to send error to things socket client: {}", err);
+    }
+}
+
+/// The status reported to a client whose request to an add-on failed.
+fn request_status(err: &RequestError) -> StatusCode {
+    StatusCode::from_u16(err.status().code).unwrap_or(StatusCode::BAD_GATEWAY)
+}
+
+fn target_thing(id: Option<String>, route: &Route) -> Result<String, ErrorMessage> {
+    match (id, route) {
+        (Some(id), _) => Ok(id),
+        (None, Route::Thing(id)) => Ok(id.to_owned()),
+        (None, Route::Things) | (None, Route::NewThings) | (None, Route::Logs) => Err(
+            ErrorMessage::new(None, StatusCode::BAD_REQUEST, "Missing thing id".to_owned()),
+        ),
+    }
+}
+
+async fn set_properties(
+    subscriber_id: u64,
+    thing_id: String,
+    values: HashMap<String, Value>,
+) -> Result<(), ErrorMessage> {
+    let error = |status, message| ErrorMessage::new(Some(thing_id.to_owned()), status, message);
+    let mut devices = call!(AddonManager.GetDevices)
+        .map_err(|err| error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    let device = devices.remove(&thing_id).ok_or_else(|| {
+        error(
+            StatusCode::NOT_FOUND,
+            format!("Thing {} is not connected", thing_id),
+        )
+    })?;
+    for (name, value) in &values {
+        device.validate_property_value(name, value).map_err(|err| {
+            error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid value for property {}: {}", name, err),
+            )
+        })?;
+    }
+
+    for (name, value) in values {
+        let request =
+            call!(AddonManager.SetPropertyValue(thing_id.to_owned(), name.to_owned(), value))
+                .map_err(|err| {
+                    ErrorMessage::new(
+                        Some(thing_id.to_owned()),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to set property {}: {}", name, err),
+                    )
+                })?;
+        let thing_id = thing_id.to_owned();
+        tokio::spawn(async move {
+            if let Err(err) = request.wait(PROPERTY_TIMEOUT).await {
+                reply(
+                    subscriber_id,
+                    ErrorMessage::new(
+                        Some(thing_id),
+                        request_status(&err),
+                        format!("Failed to set property {}: {}", name, err),
+                    ),
+                )
+                .await;
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn request_actions(
+    subscriber_id: u64,
+    thing_id: String,
+    actions: HashMap<String, ActionInput>,
+) -> Result<(), ErrorMessage> {
+    let error = |status, message| ErrorMessage::new(Some(thing_id.to_owned()), status, message);
+    let mut devices = call!(AddonManager.GetDevices)
+        .map_err(|err| error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    let device = devices.remove(&thing_id).ok_or_else(|| {
+        error(
+            StatusCode::NOT_FOUND,
+            format!("Thing {} is not connected", thing_id),
+        )
+    })?;
+    let descriptions = device.description.actions.unwrap_or_default();
+    for (name, ActionInput { input }) in actions {
+        let description = descriptions.get(&name).ok_or_else(|| {
+            error(
+                StatusCode::NOT_FOUND,
+                format!("Thing {} has no action {}", thing_id, name),
+            )
+        })?;
+        schema::validate(description.input.as_ref().unwrap_or(&Value::Null), &input).map_err(
+            |err| {
+                error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid input for action {}: {}", name, err),
+                )
+            },
+        )?;
+        let (action, request) =
+            call!(AddonManager.RequestAction(thing_id.to_owned(), name.to_owned(), input))
+                .map_err(|err| {
+                    error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to request action {}: {}", name, err),
+                    )
+                })?;
+        let thing_id = thing_id.to_owned();
+        tokio::spawn(async move {
+            if let Err(err) = confirm_action(&thing_id, &action, request, ACTION_TIMEOUT).await {
+                reply(
+                    subscriber_id,
+                    ErrorMessage::new(
+                        Some(thing_id),
+                        request_status(&err),
+                        format!("Failed to request action {}: {}", name, err),
+                    ),
+                )
+                .await;
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn handle_message(subscriber_id: u64, route: &Route, text: &str) -> Result<(), ErrorMessage> {
+    let msg: IncomingMessages = serde_json::from_str(text).map_err(|err| {
+        ErrorMessage::new(
+            None,
+            StatusCode::BAD_REQUEST,
+            format!("Invalid message: {}", err),
+        )
+    })?;
+
+    match msg {
+        IncomingMessages::SetProperty { id, data } => {
+            set_properties(subscriber_id, target_thing(id, route)?, data).await
+        }
+        IncomingMessages::RequestAction { id, data } => {
+            request_actions(subscriber_id, target_thing(id, route)?, data).await
+        }
+        IncomingMessages::AddEventSubscription { id, data } => {
+            target_thing(id, route)?;
+            for event_name in data.into_keys() {
+                if let Err(err) =
+                    call!(ThingsSocket.AddEventSubscription(subscriber_id, event_name))
+                {
+                    error!("Failed to add event subscription: {}", err);
+                }
+            }
+            Ok(())
+        }
+        IncomingMessages::Subscribe { id, data } => {
+            let thing_id = target_thing(id, route)?;
+            if let Err(err) = call!(ThingsSocket.Subscribe(subscriber_id, thing_id, data)) {
+                error!("Failed to add subscription: {}", err);
+            }
+            Ok(())
+        }
+        IncomingMessages::Unsubscribe { id } => {
+            let thing_id = target_thing(id, route)?;
+            if let Err(err) = call!(ThingsSocket.Unsubscribe(subscriber_id, thing_id)) {
+                error!("Failed to remove subscription: {}", err);
+            }
+            Ok(())
+        }
+    }
+}
+
+struct Handshake {
+    path: String,
+    token: Option<String>,
+    origin: Option<String>,
+    host: Option<String>,
+}
+
+/// Reads the HTTP upgrade request without consuming it, so it can be authorized before the
+/// websocket handshake is performed.
+async fn peek_handshake(stream: &TcpStream) -> Result<Handshake> {
+    let mut buf = vec![0; MAX_HANDSHAKE_SIZE];
+    loop {
+        let len = stream.peek(&mut buf).await?;
+        if len == 0 {
+            bail!("Connection closed during handshake");
+        }
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        if req.parse(&buf[..len])?.is_complete() {
+            let header = |name: &str| {
+                req.headers
+                    .iter()
+                    .find(|header| header.name.eq_ignore_ascii_case(name))
+                    .and_then(|header| std::str::from_utf8(header.value).ok())
+                    .map(|value| value.to_owned())
+            };
+            let path = req
+                .path
+                .ok_or_else(|| anyhow!("Failed to parse path"))?
+                .to_owned();
+            let token = header("Authorization")
+                .and_then(|value| jwt::parse_bearer(&value))
+                .or_else(|| {
+                    path.split_once('?')
+                        .and_then(|(_, query)| jwt::parse_jwt_query(query))
+                });
+            return Ok(Handshake {
+                token,
+                origin: header("Origin"),
+                host: header("Host"),
+                path,
+            });
+        }
+        if len == buf.len() {
+            bail!(
+                "Handshake exceeded max size of {} bytes",
+                MAX_HANDSHAKE_SIZE
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Requests without an `Origin` header come from non-browser clients. Browsers are only allowed
+/// from the origin serving the gateway or from one of the configured `allowedOrigins`.
+fn origin_allowed(origin: Option<&str>, host: Option<&str>, allowed_origins: &[String]) -> bool {
+    match origin {
+        None => true,
+        Some(origin) => {
+            allowed_origins.iter().any(|allowed| allowed == origin)
+                || match (origin.split_once("://"), host) {
+                    (Some((_, authority)), Some(host)) => authority == host,
+                    _ => false,
+                }
+        }
+    }
+}
+
+async fn reject(mut stream: TcpStream, status: StatusCode) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()).await {
+        debug!("Failed to reject things websocket: {}", err);
+    }
+}
+
+async fn authorize(stream: &TcpStream) -> Result<String, StatusCode> {
+    let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, peek_handshake(stream))
+        .await
+        .map_err(|_| StatusCode::REQUEST_TIMEOUT)?
+        .map_err(|err| {
+            debug!("Invalid things websocket handshake: {}", err);
+            StatusCode::BAD_REQUEST
+        })?;
+    if parse_route(&handshake.path).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !origin_allowed(
+        handshake.origin.as_deref(),
+        handshake.host.as_deref(),
+        &CONFIG.allowed_origins,
+    ) {
+        warn!(
+            "Rejecting things websocket from origin {:?}",
+            handshake.origin
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let token = handshake.token.ok_or(StatusCode::UNAUTHORIZED)?;
+    if let Err(err) = jwt::decode_token(&token).await {
+        warn!("Things websocket authorization failed: {:?}", err);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(token)
+}
+
 async fn handle_connection(stream: TcpStream, addr: SocketAddr) {
     debug!("Incoming things websocket connection from {:?}", addr);
 
-    let ws_stream = tokio_tungstenite::accept_async(stream)
-        .await
-        .expect("Error during the things websocket handshake occurred");
+    let token = match authorize(&stream).await {
+        Ok(token) => token,
+        Err(status) => {
+            reject(stream, status).await;
+            return;
+        }
+    };
+
+    let mut route = None;
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(
+        stream,
+        |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            route = parse_route(&request.uri().to_string());
+            if route.is_none() {
+                let mut response = ErrorResponse::new(Some("Unknown path".to_owned()));
+                *response.status_mut() = StatusCode::NOT_FOUND;
+                return Err(response);
+            }
+            Ok(response)
+        },
+    )
+    .await
+    {
+        Ok(ws_stream) => ws_stream,
+        Err(err) => {
+            error!("Error during the things websocket handshake: {}", err);
+            return;
+        }
+    };
+    let route = match route {
+        Some(route) => route,
+        None => return,
+    };
+
+    let (sink, mut stream) = ws_stream.split();
+
+    let (sender, receiver) = mpsc::channel(OUTGOING_QUEUE_SIZE);
+    tokio::spawn(write_messages(sink, receiver));
+    let subscriber_id = match call!(ThingsSocket.RegisterSink(sender, route.clone())) {
+        Ok(id) => id,
+        Err(err) => {
+            error!("Error sending sink to ThingsSocket: {}", err);
+            return;
+        }
+    };
+    if route == Route::NewThings {
+        if let Err(err) = send_new_things(subscriber_id).await {
+            error!("Failed to send new things: {}", err);
+        }
+    }
 
-    let (sink, _) = ws_stream.split();
+    let mut token_check = tokio::time::interval(TOKEN_CHECK_INTERVAL);
+    token_check.tick().await;
+
+    loop {
+        tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(tungstenite::Message::Text(text))) => {
+                    debug!("Received a things message from {}: {}", addr, text);
+                    if let Err(err) = handle_message(subscriber_id, &route, &text).await {
+                        reply(subscriber_id, err).await;
+                    }
+                }
+                Some(Ok(tungstenite::Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(err)) => {
+                    debug!("Things websocket of {} failed: {}", addr, err);
+                    break;
+                }
+            },
+            _ = token_check.tick() => {
+                if let Err(err) = jwt::decode_token(&token).await {
+                    info!("Closing things websocket of {}: {:?}", addr, err);
+                    if let Err(err) = call!(ThingsSocket.CloseSink(
+                        subscriber_id,
+                        "Authorization expired or revoked".to_owned()
+                    )) {
+                        error!("Error closing things websocket: {}", err);
+                    }
+                    return;
+                }
+            }
+        }
+    }
 
-    if let Err(err) = call!(ThingsSocket.RegisterSink(sink)) {
-        error!("Error sending sink to ThingsSocket: {}", err);
+    if let Err(err) = call!(ThingsSocket.UnregisterSink(subscriber_id)) {
+        error!("Error removing sink from ThingsSocket: {}", err);
     }
 }
 
@@ -122,3 +989,153 @@ pub async fn start() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_allowed() {
+        let allowed = vec!["https://dashboard.local".to_owned()];
+        assert!(origin_allowed(None, Some("gateway.local:8080"), &allowed));
+        assert!(origin_allowed(
+            Some("http://gateway.local:8080"),
+            Some("gateway.local:8080"),
+            &allowed
+        ));
+        assert!(origin_allowed(
+            Some("https://dashboard.local"),
+            Some("gateway.local:8080"),
+            &allowed
+        ));
+        assert!(!origin_allowed(
+            Some("https://evil.example"),
+            Some("gateway.local:8080"),
+            &allowed
+        ));
+        assert!(!origin_allowed(Some("https://evil.example"), None, &[]));
+    }
+
+    fn property_status(id: &str, name: &str) -> ThingsMessages {
+        ThingsMessages::PropertyStatusMessage(PropertyStatusMessage::new(
+            id.to_owned(),
+            name.to_owned(),
+            Value::Null,
+        ))
+    }
+
+    #[test]
+    fn test_subscriber_firehose() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::Things);
+        assert!(subscriber.wants(&property_status("lamp", "on")));
+        assert!(
+            subscriber.wants(&ThingsMessages::ConnectedMessage(ConnectedMessage::new(
+                "sensor".to_owned(),
+                true
+            )))
+        );
+    }
+
+    #[test]
+    fn test_subscriber_thing_route() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let mut subscriber = Subscriber::new(sender, Route::Thing("lamp".to_owned()));
+        assert!(subscriber.wants(&property_status("lamp", "on")));
+        assert!(!subscriber.wants(&property_status("sensor", "on")));
+
+        let event = ThingEvent {
+            name: "overheated".to_owned(),
+            data: Value::Null,
+            timestamp: "2021-01-01T00:00:00+00:00".to_owned(),
+        };
+        let event = ThingsMessages::EventMessage(EventMessage::new("lamp".to_owned(), event));
+        assert!(!subscriber.wants(&event));
+        subscriber
+            .event_subscriptions
+            .insert("overheated".to_owned());
+        assert!(subscriber.wants(&event));
+    }
+
+    #[test]
+    fn test_subscriber_new_things_route() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::NewThings);
+        assert!(!subscriber.wants(&property_status("lamp", "on")));
+        assert!(!subscriber.wants(&ThingsMessages::ThingRemovedMessage(
+            ThingRemovedMessage::new("lamp".to_owned())
+        )));
+    }
+
+    #[test]
+    fn test_subscriber_logs_route() {
+        let sample = ThingsMessages::LogSampleMessage(LogSampleMessage::new(
+            "sensor".to_owned(),
+            "temperature".to_owned(),
+            Sample {
+                time: 0,
+                value: Value::from(21),
+            },
+        ));
+        let (sender, _receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::Logs);
+        assert!(subscriber.wants(&sample));
+        assert!(!subscriber.wants(&property_status("sensor", "temperature")));
+        let (sender, _receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::Things);
+        assert!(!subscriber.wants(&sample));
+    }
+
+    #[test]
+    fn test_subscriber_subscriptions() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let mut subscriber = Subscriber::new(sender, Route::Things);
+        let mut subscriptions = HashMap::new();
+        subscriptions.insert("lamp".to_owned(), None);
+        let mut properties = HashSet::new();
+        properties.insert("temperature".to_owned());
+        subscriptions.insert("sensor".to_owned(), Some(properties));
+        subscriber.subscriptions = Some(subscriptions);
+
+        assert!(subscriber.wants(&property_status("lamp", "on")));
+        assert!(subscriber.wants(&property_status("sensor", "temperature")));
+        assert!(!subscriber.wants(&property_status("sensor", "humidity")));
+        assert!(!subscriber.wants(&property_status("switch", "on")));
+    }
+
+    #[test]
+    fn test_subscriber_send() {
+        let (sender, receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::Things);
+        assert!(subscriber.send(tungstenite::Message::Text("first".to_owned())));
+        drop(receiver);
+        assert!(!subscriber.send(tungstenite::Message::Text("closed".to_owned())));
+    }
+
+    #[test]
+    fn test_drop_slow_subscriber() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let subscriber = Subscriber::new(sender, Route::Things);
+        assert!(subscriber.send(tungstenite::Message::Text("first".to_owned())));
+        assert!(!subscriber.send(tungstenite::Message::Text("second".to_owned())));
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(parse_route("/"), Some(Route::Things));
+        assert_eq!(parse_route("/things"), Some(Route::Things));
+        assert_eq!(parse_route("/things/?jwt=abc"), Some(Route::Things));
+        assert_eq!(
+            parse_route("/things/mock-device"),
+            Some(Route::Thing("mock-device".to_owned()))
+        );
+        assert_eq!(
+            parse_route("/things/mock%20device?jwt=abc"),
+            Some(Route::Thing("mock device".to_owned()))
+        );
+        assert_eq!(parse_route("/new_things"), Some(Route::NewThings));
+        assert_eq!(parse_route("/logs"), Some(Route::Logs));
+        assert_eq!(parse_route("/things/mock-device/properties"), None);
+        assert_eq!(parse_route("/other"), None);
+    }
+}
@@ -29,6 +29,16 @@ lazy_static! {
         DIR_BUILDER.create(&path).unwrap();
         path
     };
+    pub static ref ADDONS_STAGING_DIR: PathBuf = {
+        let path = BASE_DIR.join("addons.staging");
+        DIR_BUILDER.create(&path).unwrap();
+        path
+    };
+    pub static ref ADDONS_BACKUP_DIR: PathBuf = {
+        let path = BASE_DIR.join("addons.backup");
+        DIR_BUILDER.create(&path).unwrap();
+        path
+    };
     pub static ref DATA_DIR: PathBuf = {
         let path = BASE_DIR.join("data");
         DIR_BUILDER.create(&path).unwrap();
@@ -4,10 +4,16 @@ use futures::{
     channel::mpsc, io::BufReader, stream::SplitSink, AsyncBufReadExt, AsyncRead, SinkExt, StreamExt,
 };
 use regex::Regex;
-use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use reqwest::{
+    multipart::{Form, Part},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
 use serde::Serialize;
 use serde_json::json;
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 use tempdir::TempDir;
 use tokio::{
     net::TcpStream,
@@ -157,6 +163,28 @@ impl Gateway {
             .await
     }
 
+    pub async fn patch<U: FromResponseBody>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, U) {
+        RequestBuilder::build_from(self, Method::PATCH, path)
+            .add_authorization(self)
+            .body(serde_json::to_string(&body).expect("Serialize body"))
+            .send_req()
+            .await
+    }
+
+    pub async fn upload<U: FromResponseBody>(&self, path: &str, file: &Path) -> (StatusCode, U) {
+        let content = fs::read(file).expect("Read file");
+        let form = Form::new().part("file", Part::bytes(content).file_name("package.tar.gz"));
+        RequestBuilder::build_from(self, Method::POST, path)
+            .add_authorization(self)
+            .multipart(form)
+            .send_req()
+            .await
+    }
+
     pub async fn delete<U: FromResponseBody>(&self, path: &str) -> (StatusCode, U) {
         RequestBuilder::build_from(self, Method::DELETE, path)
             .add_authorization(self)
@@ -164,6 +192,18 @@ impl Gateway {
             .await
     }
 
+    pub async fn connect_things_socket(
+        &self,
+        path: &str,
+    ) -> WebSocketStream<MaybeTlsStream<TcpStream>> {
+        let mut url = format!("ws://127.0.0.1:8082{}", path);
+        if let Some(jwt) = &self.jwt {
+            url = format!("{}?jwt={}", url, jwt);
+        }
+        let (socket, _) = connect_async(url).await.expect("Connect to things socket");
+        socket
+    }
+
     pub async fn create_secondary_user(&self) {
         self.post::<serde_json::Value>(
             "/users",
@@ -173,6 +213,42 @@ impl Gateway {
     }
 }
 
+/// Packs the mock add-on like a published add-on and returns the path of the `.tar.gz` file.
+pub async fn mock_addon_package(dir: &Path) -> PathBuf {
+    mock_addon_package_with(dir, |_| {}).await
+}
+
+/// Packs the mock add-on with a manifest changed by `edit_manifest`.
+pub async fn mock_addon_package_with(
+    dir: &Path,
+    edit_manifest: impl FnOnce(&mut serde_json::Value),
+) -> PathBuf {
+    let mock_addon_source_dir = env::current_dir().unwrap().join("mock-addon");
+    Command::new("bash")
+        .args(vec!["copy.sh", dir.to_str().unwrap()])
+        .current_dir(mock_addon_source_dir)
+        .output()
+        .await
+        .unwrap();
+    fs::rename(dir.join("mock-addon"), dir.join("package")).expect("Rename package dir");
+
+    let manifest_path = dir.join("package").join("manifest.json");
+    let mut manifest =
+        serde_json::from_str(&fs::read_to_string(&manifest_path).expect("Read manifest"))
+            .expect("Parse manifest");
+    edit_manifest(&mut manifest);
+    fs::write(&manifest_path, manifest.to_string()).expect("Write manifest");
+
+    let package = dir.join("mock-addon.tar.gz");
+    Command::new("tar")
+        .args(vec!["czf", package.to_str().unwrap(), "package"])
+        .current_dir(dir)
+        .output()
+        .await
+        .unwrap();
+    package
+}
+
 fn create_dirs() -> Dirs {
     let home_dir = TempDir::new(".webthingsio").expect("Create home dir");
 
@@ -196,6 +272,10 @@ async fn start_gateway(dirs: &Dirs) -> Child {
         .stderr(Stdio::piped())
         .env("WEBTHINGS_HOME", dirs.home_dir().into_os_string())
         .env("WEBTHINGS_UI", dirs.ui_dir().into_os_string())
+        .env(
+            "WEBTHINGS_CONFIG",
+            json!({"addonManager": {"listUrls": [], "registrationTimeout": 5}}).to_string(),
+        )
         .spawn()
         .expect("Start gateway process")
 }
@@ -246,6 +326,13 @@ impl FromResponseBody for serde_json::Value {
     }
 }
 
+#[async_trait]
+impl FromResponseBody for Response {
+    async fn from_response_body(response: Response) -> Self {
+        response
+    }
+}
+
 #[async_trait]
 pub trait GatewayRequest {
     fn build_from(gateway: &Gateway, method: Method, route: &str) -> Self;
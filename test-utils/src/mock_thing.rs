@@ -36,8 +36,19 @@ pub fn property(name: &str, type_: &str) -> webthings_gateway_ipc_types::Propert
     }
 }
 
+pub fn action() -> webthings_gateway_ipc_types::Action {
+    webthings_gateway_ipc_types::Action {
+        at_type: None,
+        description: None,
+        input: None,
+        links: None,
+        title: None,
+    }
+}
+
 pub trait DeviceExt {
     fn add_property(&mut self, property: webthings_gateway_ipc_types::Property);
+    fn add_action(&mut self, name: &str, action: webthings_gateway_ipc_types::Action);
 }
 
 impl DeviceExt for webthings_gateway_ipc_types::Device {
@@ -50,4 +61,10 @@ impl DeviceExt for webthings_gateway_ipc_types::Device {
             self.properties = Some(properties);
         }
     }
+
+    fn add_action(&mut self, name: &str, action: webthings_gateway_ipc_types::Action) {
+        self.actions
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.to_owned(), action);
+    }
 }
@@ -5,18 +5,32 @@ use test_utils::gateway::{Gateway, GatewayRequest};
 
 extern crate serial_test;
 
+#[path = "rest_api/addons.rs"]
+mod addons;
 #[path = "rest_api/extensions.rs"]
 mod extensions;
 #[path = "rest_api/login.rs"]
 mod login;
+#[path = "rest_api/logs.rs"]
+mod logs;
 #[path = "rest_api/new_things.rs"]
 mod new_things;
+#[path = "rest_api/notifiers.rs"]
+mod notifiers;
 #[path = "rest_api/ping.rs"]
 mod ping;
+#[path = "rest_api/rules.rs"]
+mod rules;
+#[path = "rest_api/scenes.rs"]
+mod scenes;
+#[path = "rest_api/schedules.rs"]
+mod schedules;
 #[path = "rest_api/settings.rs"]
 mod settings;
 #[path = "rest_api/things.rs"]
 mod things;
+#[path = "rest_api/things_socket.rs"]
+mod things_socket;
 #[path = "rest_api/users.rs"]
 mod users;
 
@@ -37,6 +51,15 @@ async fn test_protected_routes() {
 
     let protected_routes = vec![
         (Method::GET, "/addons", json!({})),
+        (Method::GET, "/addons/available", json!({})),
+        (Method::POST, "/addons/upload", json!({})),
+        (
+            Method::POST,
+            "/addons/local",
+            json!({"path": "/nonexistent.tar.gz"}),
+        ),
+        (Method::GET, "/addons/updates", json!({})),
+        (Method::GET, "/addons/jobs/1", json!({})),
         (Method::PUT, "/addons/an_addon", json!({"enabled": true})),
         (
             Method::PUT,
@@ -46,6 +69,7 @@ async fn test_protected_routes() {
         (Method::GET, "/addons/an_addon/config", json!({})),
         (Method::GET, "/addons/an_addon/license", json!({})),
         (Method::DELETE, "/addons/an_addon", json!({})),
+        (Method::POST, "/addons/an_addon/rollback", json!({})),
         (
             Method::POST,
             "/addons",
@@ -57,13 +81,112 @@ async fn test_protected_routes() {
             json!({"url": "", "checksum": ""}),
         ),
         (Method::GET, "/extensions", json!({})),
+        (Method::GET, "/extensions/an_addon/api/a_path", json!({})),
+        (Method::POST, "/extensions/an_addon/api/a_path", json!({})),
+        (Method::DELETE, "/extensions/an_addon/api/a_path", json!({})),
         (Method::GET, "/settings/localization/language", json!({})),
         (Method::GET, "/settings/localization/units", json!({})),
         (Method::GET, "/settings/localization/timezone", json!({})),
+        (
+            Method::PUT,
+            "/settings/localization/timezone",
+            json!({"zone": "Europe/Berlin"}),
+        ),
+        (Method::GET, "/settings/localization/location", json!({})),
+        (
+            Method::PUT,
+            "/settings/localization/location",
+            json!({"latitude": 0.0, "longitude": 0.0}),
+        ),
         (Method::GET, "/settings/addonsInfo", json!({})),
         (Method::GET, "/things", json!({})),
         (Method::GET, "/things/a_thing", json!({})),
+        (Method::PUT, "/things/a_thing", json!({})),
+        (Method::PATCH, "/things/a_thing", json!({})),
+        (Method::DELETE, "/things/a_thing", json!({})),
+        (Method::GET, "/things/a_thing/properties", json!({})),
+        (
+            Method::GET,
+            "/things/a_thing/properties/a_property",
+            json!({}),
+        ),
+        (
+            Method::PUT,
+            "/things/a_thing/properties/a_property",
+            json!(42),
+        ),
+        (Method::GET, "/things/a_thing/actions", json!({})),
+        (Method::GET, "/things/a_thing/actions/an_action", json!({})),
+        (
+            Method::POST,
+            "/things/a_thing/actions/an_action",
+            json!({"an_action": {"input": {}}}),
+        ),
+        (
+            Method::DELETE,
+            "/things/a_thing/actions/an_action/an_id",
+            json!({}),
+        ),
+        (Method::GET, "/things/a_thing/events", json!({})),
+        (Method::GET, "/things/a_thing/events/an_event", json!({})),
+        (Method::GET, "/logs", json!({})),
+        (
+            Method::POST,
+            "/logs",
+            json!({"thing": "a_thing", "property": "a_property", "maxAge": 1}),
+        ),
+        (Method::DELETE, "/logs/1", json!({})),
+        (
+            Method::GET,
+            "/logs/things/a_thing/properties/a_property",
+            json!({}),
+        ),
         (Method::GET, "/new_things", json!({})),
+        (Method::POST, "/new_things/pairing", json!({"timeout": 1})),
+        (Method::DELETE, "/new_things/pairing", json!({})),
+        (Method::GET, "/notifiers", json!({})),
+        (Method::GET, "/notifiers/a_notifier/outlets", json!({})),
+        (
+            Method::POST,
+            "/notifiers/a_notifier/outlets/an_outlet/notify",
+            json!({"title": "", "message": ""}),
+        ),
+        (Method::GET, "/rules", json!({})),
+        (Method::GET, "/rules/1", json!({})),
+        (
+            Method::POST,
+            "/rules",
+            json!({"name": "", "trigger": {"type": "or", "triggers": []}, "effects": []}),
+        ),
+        (
+            Method::PUT,
+            "/rules/1",
+            json!({"name": "", "trigger": {"type": "or", "triggers": []}, "effects": []}),
+        ),
+        (Method::DELETE, "/rules/1", json!({})),
+        (Method::GET, "/scenes", json!({})),
+        (Method::GET, "/scenes/1", json!({})),
+        (
+            Method::POST,
+            "/scenes",
+            json!({"name": "", "properties": {}}),
+        ),
+        (Method::PUT, "/scenes/1", json!({"name": "", "values": {}})),
+        (Method::DELETE, "/scenes/1", json!({})),
+        (Method::POST, "/scenes/1/apply", json!({})),
+        (Method::GET, "/schedules", json!({})),
+        (Method::GET, "/schedules/1", json!({})),
+        (
+            Method::POST,
+            "/schedules",
+            json!({"name": "", "timing": {"type": "sunset"}, "effects": []}),
+        ),
+        (
+            Method::PUT,
+            "/schedules/1",
+            json!({"name": "", "timing": {"type": "sunset"}, "effects": []}),
+        ),
+        (Method::DELETE, "/schedules/1", json!({})),
         (Method::GET, "/users/info", json!({})),
         (Method::GET, "/users/a_user", json!({})),
         (
@@ -0,0 +1,145 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::time::Duration;
+use tempdir::TempDir;
+use test_utils::{
+    gateway::{mock_addon_package, mock_addon_package_with, Gateway, MockAddonConnection},
+    mock_thing,
+};
+use tokio::time::sleep;
+
+#[tokio::test]
+#[serial]
+async fn get_pending_updates_of_unlisted_addon() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+
+    let (status, response) = gateway.get::<Value>("/addons/updates").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn upload_addon() {
+    let dir = TempDir::new("package").unwrap();
+    let package = mock_addon_package(dir.path()).await;
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.upload::<Value>("/addons/upload", &package).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["id"], json!("mock-addon"));
+    assert_eq!(response["enabled"], json!(true));
+
+    let (_, response) = gateway.get::<Value>("/addons").await;
+    assert_eq!(response[0]["id"], json!("mock-addon"));
+}
+
+#[tokio::test]
+#[serial]
+async fn install_local_addon() {
+    let dir = TempDir::new("package").unwrap();
+    let package = mock_addon_package(dir.path()).await;
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway
+        .post::<Value>("/addons/local", json!({ "path": package }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["id"], json!("mock-addon"));
+}
+
+#[tokio::test]
+#[serial]
+async fn install_missing_local_addon() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>("/addons/local", json!({"path": "/nonexistent.tar.gz"}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn rollback_updated_addon() {
+    let dir = TempDir::new("package").unwrap();
+    let package = mock_addon_package(dir.path()).await;
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>("/addons/mock-addon/rollback", json!({}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    gateway.upload::<Value>("/addons/upload", &package).await;
+    gateway.upload::<Value>("/addons/upload", &package).await;
+    let (status, response) = gateway
+        .post::<Value>("/addons/mock-addon/rollback", json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["id"], json!("mock-addon"));
+
+    let (status, _) = gateway
+        .post::<String>("/addons/mock-addon/rollback", json!({}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn roll_back_addon_which_does_not_register() {
+    let dir = TempDir::new("package").unwrap();
+    let package = mock_addon_package(dir.path()).await;
+    let broken_dir = TempDir::new("package").unwrap();
+    let broken_package = mock_addon_package_with(broken_dir.path(), |manifest| {
+        manifest["version"] = json!("2.0.0");
+        manifest["gateway_specific_settings"]["webthings"]["exec"] = json!("false");
+    })
+    .await;
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway.upload::<Value>("/addons/upload", &package).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, response) = gateway
+        .upload::<Value>("/addons/upload", &broken_package)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["version"], json!("2.0.0"));
+
+    loop {
+        let (_, response) = gateway.get::<Value>("/addons").await;
+        if response[0]["version"] == json!("1.0.0") {
+            assert_eq!(response[0]["enabled"], json!(true));
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    let mut addon = MockAddonConnection::new().await;
+    addon
+        .create_mock_device(mock_thing::device("mock-device"))
+        .await;
+    loop {
+        let (status, _) = gateway.get::<Value>("/things/mock-device/properties").await;
+        if status == StatusCode::OK {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn get_unknown_job() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway.get::<String>("/addons/jobs/42").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -1,4 +1,4 @@
-use reqwest::StatusCode;
+use reqwest::{Response, StatusCode};
 use serde_json::{json, Value};
 use serial_test::serial;
 use test_utils::gateway::Gateway;
@@ -12,3 +12,80 @@ async fn get_extensions() {
     assert_eq!(status, StatusCode::OK);
     assert_eq!(response, json!({}));
 }
+
+#[tokio::test]
+#[serial]
+async fn get_extensions_of_mock_addon() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+    let (status, response) = gateway.get::<Value>("/extensions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!({"mock-addon": [{"js": ["js/extension.js"]}]})
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn get_extension_resource() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+    let (status, response) = gateway
+        .get::<String>("/extensions/mock-addon/js/extension.js")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, "console.log('mock extension');\n");
+
+    let (status, _) = gateway
+        .get::<String>("/extensions/mock-addon/manifest.json")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn proxy_without_api_handler() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+    let (status, _) = gateway
+        .get::<String>("/extensions/unknown-addon/api/status")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn proxy_to_api_handler() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+
+    let (status, response) = gateway
+        .get::<Response>("/extensions/mock-addon/api/echo/status?verbose=true")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({
+            "method": "GET",
+            "path": "/echo/status",
+            "query": {"verbose": "true"},
+            "body": {}
+        })
+    );
+
+    let (status, response) = gateway
+        .post::<Response>(
+            "/extensions/mock-addon/api/echo?id=42",
+            json!({"name": "Mock", "level": 3}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({
+            "method": "POST",
+            "path": "/echo",
+            "query": {"id": "42"},
+            "body": {"name": "Mock", "level": 3}
+        })
+    );
+}
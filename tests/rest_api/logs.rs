@@ -0,0 +1,116 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use test_utils::gateway::Gateway;
+
+#[tokio::test]
+#[serial]
+async fn log_unknown_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/logs",
+            json!({"thing": "mock-device", "property": "mock-property", "maxAge": 60000}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn create_and_delete_log() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    gateway
+        .post::<String>(
+            "/things",
+            json!({
+                "id": "mock-device",
+                "title": "Mock Device",
+                "properties": {
+                    "mock-property": {"name": "mock-property", "type": "number"}
+                }
+            }),
+        )
+        .await;
+
+    let (status, log) = gateway
+        .post::<Value>(
+            "/logs",
+            json!({"thing": "mock-device", "property": "mock-property", "maxAge": 60000}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(log["thing"], json!("mock-device"));
+    assert_eq!(log["property"], json!("mock-property"));
+
+    let (status, response) = gateway.get::<Value>("/logs").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([log]));
+
+    let (status, response) = gateway
+        .get::<Value>("/logs/things/mock-device/properties/mock-property?start=0")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+
+    let path = format!("/logs/{}", log["id"]);
+    let (status, _) = gateway.delete::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = gateway.delete::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = gateway
+        .get::<Value>("/logs/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn log_with_too_long_retention() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/logs",
+            json!({"thing": "mock-device", "property": "mock-property", "maxAge": u64::MAX}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn delete_logs_of_deleted_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    gateway
+        .post::<String>(
+            "/things",
+            json!({
+                "id": "mock-device",
+                "title": "Mock Device",
+                "properties": {
+                    "mock-property": {"name": "mock-property", "type": "number"}
+                }
+            }),
+        )
+        .await;
+    let (status, _) = gateway
+        .post::<Value>(
+            "/logs",
+            json!({"thing": "mock-device", "property": "mock-property", "maxAge": 60000}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = gateway.delete::<String>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, response) = gateway.get::<Value>("/logs").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
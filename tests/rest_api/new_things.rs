@@ -44,3 +44,30 @@ async fn get_new_things() {
         }])
     );
 }
+
+#[tokio::test]
+#[serial]
+async fn start_and_cancel_pairing() {
+    let (gateway, _addon) = Gateway::startup_with_mock_addon().await;
+
+    let (status, response) = gateway
+        .post::<Value>("/new_things/pairing", json!({"timeout": 30}))
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(response["timeout"], json!(30));
+
+    let (status, _) = gateway.delete::<Value>("/new_things/pairing").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+#[serial]
+async fn start_pairing_with_invalid_timeout() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<Value>("/new_things/pairing", json!({"timeout": 0}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,70 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use test_utils::gateway::Gateway;
+
+#[tokio::test]
+#[serial]
+async fn get_notifiers_empty() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.get::<Value>("/notifiers").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn get_outlets_of_unknown_notifier() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway.get::<String>("/notifiers/email/outlets").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn notify_unknown_notifier() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/notifiers/email/outlets/admin/notify",
+            json!({"title": "Doorbell", "message": "Someone is at the door", "level": 2}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn notify_mock_outlet() {
+    let (gateway, _) = Gateway::startup_with_mock_addon().await;
+
+    let (status, response) = gateway.get::<Value>("/notifiers").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!([{"id": "mock-notifier", "name": "Mock notifier"}])
+    );
+
+    let (status, response) = gateway
+        .get::<Value>("/notifiers/mock-notifier/outlets")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!([{"id": "mock-outlet", "name": "Mock outlet"}])
+    );
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/notifiers/mock-notifier/outlets/mock-outlet/notify",
+            json!({"title": "Doorbell", "message": "Someone is at the door", "level": 2}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,144 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::time::Duration;
+use test_utils::{
+    gateway::Gateway,
+    mock_thing::{self, DeviceExt},
+};
+use tokio::time::sleep;
+
+fn rule() -> Value {
+    json!({
+        "name": "Night light",
+        "trigger": {
+            "type": "propertyEquals",
+            "thing": "switch",
+            "property": "on",
+            "value": true
+        },
+        "effects": [{
+            "type": "setProperty",
+            "thing": "lamp",
+            "property": "on",
+            "value": true
+        }]
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn get_rules_empty() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.get::<Value>("/rules").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn create_update_and_delete_rule() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, created) = gateway.post::<Value>("/rules", rule()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["name"], json!("Night light"));
+    assert_eq!(created["enabled"], json!(true));
+    let path = format!("/rules/{}", created["id"]);
+
+    let (status, response) = gateway.get::<Value>(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, created);
+
+    let mut changed = rule();
+    changed["enabled"] = json!(false);
+    let (status, response) = gateway.put::<Value>(&path, changed).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["enabled"], json!(false));
+
+    let (status, response) = gateway.get::<Value>("/rules").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.as_array().unwrap().len(), 1);
+    assert_eq!(response[0]["enabled"], json!(false));
+
+    let (status, _) = gateway.delete::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = gateway.get::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn create_invalid_rule() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/rules",
+            json!({"name": "Broken", "trigger": {"type": "unknown"}, "effects": []}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[serial]
+async fn rule_sets_property() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("switch", "boolean"));
+    device.add_property(mock_thing::property("lamp", "boolean"));
+    let mut level = mock_thing::property("level", "integer");
+    level.value = Some(json!(30));
+    device.add_property(level);
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .post::<Value>(
+            "/rules",
+            json!({
+                "name": "Bright light",
+                "trigger": {
+                    "type": "and",
+                    "triggers": [{
+                        "type": "propertyEquals",
+                        "thing": "mock-device",
+                        "property": "switch",
+                        "value": true
+                    }, {
+                        "type": "propertyAbove",
+                        "thing": "mock-device",
+                        "property": "level",
+                        "threshold": 20
+                    }]
+                },
+                "effects": [{
+                    "type": "setProperty",
+                    "thing": "mock-device",
+                    "property": "lamp",
+                    "value": true
+                }]
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device/properties/switch", json!(true))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    loop {
+        let (status, response) = gateway
+            .get::<Value>("/things/mock-device/properties/lamp")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        if response == json!(true) {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+}
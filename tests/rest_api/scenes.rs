@@ -0,0 +1,155 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use test_utils::{
+    gateway::Gateway,
+    mock_thing::{self, DeviceExt},
+};
+
+#[tokio::test]
+#[serial]
+async fn get_scenes_empty() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.get::<Value>("/scenes").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn capture_and_apply_scene() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+    let (status, scene) = gateway
+        .post::<Value>(
+            "/scenes",
+            json!({"name": "Movie night", "properties": {"mock-device": ["mock-property"]}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        scene["values"],
+        json!({"mock-device": {"mock-property": 42}})
+    );
+
+    gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(7))
+        .await;
+    let path = format!("/scenes/{}/apply", scene["id"]);
+    let (status, response) = gateway.post::<Value>(&path, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!([{"thing": "mock-device", "property": "mock-property", "success": true}])
+    );
+    let (_, value) = gateway
+        .get::<Value>("/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(value, json!(42));
+}
+
+#[tokio::test]
+#[serial]
+async fn apply_scene_with_disconnected_thing() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(1))
+        .await;
+    let (_, scene) = gateway
+        .post::<Value>(
+            "/scenes",
+            json!({"name": "Reading", "properties": {"mock-device": []}}),
+        )
+        .await;
+    let path = format!("/scenes/{}", scene["id"]);
+    let (status, _) = gateway
+        .put::<Value>(
+            &path,
+            json!({
+                "name": "Reading",
+                "values": {
+                    "mock-device": {"mock-property": 3},
+                    "missing-device": {"on": true}
+                }
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, response) = gateway
+        .post::<Value>(&format!("{}/apply", path), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let results = response.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["thing"], json!("missing-device"));
+    assert_eq!(results[0]["success"], json!(false));
+    assert!(results[0]["error"].is_string());
+    assert_eq!(results[1]["thing"], json!("mock-device"));
+    assert_eq!(results[1]["success"], json!(true));
+
+    let (status, _) = gateway.delete::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = gateway.get::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn capture_properties_without_value() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    device.add_property(mock_thing::property("unset-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(5))
+        .await;
+    let (status, scene) = gateway
+        .post::<Value>(
+            "/scenes",
+            json!({"name": "Reading", "properties": {"mock-device": []}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        scene["values"],
+        json!({"mock-device": {"mock-property": 5}})
+    );
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/scenes",
+            json!({"name": "Reading", "properties": {"mock-device": ["unset-property"]}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn capture_disconnected_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/scenes",
+            json!({"name": "Movie night", "properties": {"mock-device": []}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,127 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::time::Duration;
+use test_utils::{
+    gateway::Gateway,
+    mock_thing::{self, DeviceExt},
+};
+use tokio::time::sleep;
+
+fn schedule() -> Value {
+    json!({
+        "name": "Porch light at dusk",
+        "timing": {
+            "type": "sunset",
+            "offset": -15
+        },
+        "effects": [{
+            "type": "setProperty",
+            "thing": "porch",
+            "property": "on",
+            "value": true
+        }]
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn get_schedules_empty() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.get::<Value>("/schedules").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn create_update_and_delete_schedule() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, created) = gateway.post::<Value>("/schedules", schedule()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["name"], json!("Porch light at dusk"));
+    assert_eq!(created["enabled"], json!(true));
+    let path = format!("/schedules/{}", created["id"]);
+
+    let (status, response) = gateway.get::<Value>(&path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, created);
+
+    let mut changed = schedule();
+    changed["timing"] = json!({"type": "cron", "expression": "0 0 22 * * *"});
+    let (status, response) = gateway.put::<Value>(&path, changed).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["timing"]["type"], json!("cron"));
+
+    let (status, _) = gateway.delete::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = gateway.get::<Value>(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn create_invalid_schedule() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let mut invalid = schedule();
+    invalid["timing"] = json!({"type": "cron", "expression": "at dusk"});
+    let (status, _) = gateway.post::<String>("/schedules", invalid).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut invalid = schedule();
+    invalid["timing"] = json!({"type": "once", "time": "tomorrow"});
+    let (status, _) = gateway.post::<String>("/schedules", invalid).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn run_one_time_schedule() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("lamp", "boolean"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let time = chrono::Utc::now() + chrono::Duration::seconds(2);
+    let (status, created) = gateway
+        .post::<Value>(
+            "/schedules",
+            json!({
+                "name": "Lamp in a moment",
+                "timing": {
+                    "type": "once",
+                    "time": time.to_rfc3339()
+                },
+                "effects": [{
+                    "type": "setProperty",
+                    "thing": "mock-device",
+                    "property": "lamp",
+                    "value": true
+                }]
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    loop {
+        let (status, response) = gateway
+            .get::<Value>("/things/mock-device/properties/lamp")
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        if response == json!(true) {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    let (status, response) = gateway
+        .get::<Value>(&format!("/schedules/{}", created["id"]))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["enabled"], json!(false));
+}
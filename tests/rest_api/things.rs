@@ -1,7 +1,12 @@
-use reqwest::StatusCode;
+use reqwest::{Response, StatusCode};
 use serde_json::{json, Value};
 use serial_test::serial;
-use test_utils::gateway::Gateway;
+use std::time::Duration;
+use test_utils::{
+    gateway::{Gateway, MockAddonConnection},
+    mock_thing::{self, DeviceExt},
+};
+use tokio::time::sleep;
 
 #[tokio::test]
 #[serial]
@@ -36,13 +41,51 @@ async fn get_things() {
     assert_eq!(
         response,
         json!([{
-            "connected": true,
+            "connected": false,
             "id": "mock-device",
             "title": "Mock Device",
         }])
     );
 }
 
+#[tokio::test]
+#[serial]
+async fn get_connected_thing() {
+    let mut device = mock_thing::device("mock-device");
+    let mut property = mock_thing::property("mock-property", "integer");
+    property.value = Some(json!(21));
+    device.add_property(property);
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({
+                "id": "mock-device",
+                "title": "Mock Device",
+                "properties": {
+                    "mock-property": {"name": "mock-property", "type": "integer", "value": 0}
+                }
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["connected"], json!(true));
+    assert_eq!(response["properties"]["mock-property"]["value"], json!(21));
+
+    let (status, response) = gateway.get::<Value>("/things").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response[0]["connected"], json!(true));
+    assert_eq!(
+        response[0]["properties"]["mock-property"]["value"],
+        json!(21)
+    );
+}
+
 #[tokio::test]
 #[serial]
 async fn get_unknown_thing() {
@@ -74,9 +117,436 @@ async fn get_thing() {
     assert_eq!(
         response,
         json!({
-            "connected": true,
+            "connected": false,
             "id": "mock-device",
             "title": "Mock Device",
         })
     );
 }
+
+#[tokio::test]
+#[serial]
+async fn put_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device", json!({"title": "Renamed"}))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({"id": "mock-device", "title": "Mock Device"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, response) = gateway
+        .put::<Value>(
+            "/things/mock-device",
+            json!({"title": "Renamed", "description": "A renamed device"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!({
+            "connected": false,
+            "id": "mock-device",
+            "title": "Renamed",
+            "description": "A renamed device",
+        })
+    );
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["title"], json!("Renamed"));
+}
+
+#[tokio::test]
+#[serial]
+async fn patch_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({"id": "mock-device", "title": "Mock Device"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, response) = gateway
+        .patch::<Value>(
+            "/things/mock-device",
+            json!({
+                "id": "other-device",
+                "connected": true,
+                "staleProperties": {"on": "2021-01-01T00:00:00+00:00"},
+                "selectedCapability": "Light",
+                "floorplanX": 12.5,
+                "floorplanY": 40.0,
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        response,
+        json!({
+            "connected": false,
+            "id": "mock-device",
+            "title": "Mock Device",
+            "selectedCapability": "Light",
+            "floorplanX": 12.5,
+            "floorplanY": 40.0,
+        })
+    );
+
+    let (status, _) = gateway
+        .patch::<Value>("/things/mock-device", json!({"floorplanX": "left"}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn delete_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, _) = gateway.delete::<Value>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({"id": "mock-device", "title": "Mock Device"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = gateway.delete::<Value>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = gateway.get::<Value>("/things/mock-device").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, response) = gateway.get::<Value>("/things").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn get_property_of_unknown_thing() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    let (status, _) = gateway
+        .get::<Value>("/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn get_properties() {
+    let mut device = mock_thing::device("mock-device");
+    let mut property = mock_thing::property("mock-property", "integer");
+    property.value = Some(json!(21));
+    device.add_property(property);
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device/properties").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!({"mock-property": 21}));
+
+    let (status, response) = gateway
+        .get::<Value>("/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!(21));
+}
+
+#[tokio::test]
+#[serial]
+async fn get_stale_properties() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({
+                "id": "mock-device",
+                "title": "Mock Device",
+                "properties": {
+                    "mock-property": {"name": "mock-property", "type": "integer"}
+                }
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = gateway
+        .put::<String>("/addons/mock-addon", json!({"enabled": false}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    loop {
+        let (_, thing) = gateway.get::<Value>("/things/mock-device").await;
+        if thing["staleProperties"]["mock-property"].is_string() {
+            assert_eq!(thing["connected"], json!(false));
+            assert_eq!(thing["properties"]["mock-property"]["value"], json!(42));
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let (status, response) = gateway
+        .get::<Response>("/things/mock-device/properties")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.headers()["Stale-Properties"], "mock-property");
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({"mock-property": 42})
+    );
+
+    let (status, response) = gateway
+        .get::<Response>("/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.headers()["Stale-Properties"], "mock-property");
+    assert_eq!(response.json::<Value>().await.unwrap(), json!(42));
+}
+
+#[tokio::test]
+#[serial]
+async fn get_unreported_properties_of_connected_thing() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device.clone()).await;
+    let (status, _) = gateway
+        .post::<String>(
+            "/things",
+            json!({
+                "id": "mock-device",
+                "title": "Mock Device",
+                "properties": {
+                    "mock-property": {"name": "mock-property", "type": "integer"}
+                }
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+
+    gateway
+        .put::<String>("/addons/mock-addon", json!({"enabled": false}))
+        .await;
+    loop {
+        let (_, thing) = gateway.get::<Value>("/things/mock-device").await;
+        if thing["connected"] == json!(false) {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    gateway
+        .put::<String>("/addons/mock-addon", json!({"enabled": true}))
+        .await;
+    let mut addon = MockAddonConnection::new().await;
+    addon.create_mock_device(device).await;
+    loop {
+        let (_, thing) = gateway.get::<Value>("/things/mock-device").await;
+        if thing["connected"] == json!(true) {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let (status, response) = gateway
+        .get::<Response>("/things/mock-device/properties")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response.headers()["Stale-Properties"], "mock-property");
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({"mock-property": 42})
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn put_property() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, response) = gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!(42));
+
+    let (status, response) = gateway
+        .get::<Value>("/things/mock-device/properties/mock-property")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!(42));
+}
+
+#[tokio::test]
+#[serial]
+async fn put_invalid_property_value() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!("42"))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn put_read_only_property() {
+    let mut device = mock_thing::device("mock-device");
+    let mut property = mock_thing::property("mock-property", "integer");
+    property.read_only = Some(true);
+    device.add_property(property);
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[serial]
+async fn put_unknown_property() {
+    let device = mock_thing::device("mock-device");
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .put::<Value>("/things/mock-device/properties/mock-property", json!(42))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn get_actions_empty() {
+    let device = mock_thing::device("mock-device");
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device/actions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn post_unknown_action() {
+    let device = mock_thing::device("mock-device");
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .post::<Value>(
+            "/things/mock-device/actions/mock-action",
+            json!({"mock-action": {"input": {}}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn delete_unknown_action() {
+    let device = mock_thing::device("mock-device");
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, _) = gateway
+        .delete::<Value>("/things/mock-device/actions/mock-action/1234")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[serial]
+async fn request_and_delete_action() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_action("mock-action", mock_thing::action());
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+
+    let (status, response) = gateway
+        .post::<Value>(
+            "/things/mock-device/actions/mock-action",
+            json!({"mock-action": {}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let href = response["mock-action"]["href"].as_str().unwrap().to_owned();
+    assert!(href.starts_with("/things/mock-device/actions/mock-action/"));
+
+    loop {
+        let (status, response) = gateway.get::<Value>("/things/mock-device/actions").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.as_array().unwrap().len(), 1);
+        assert_eq!(response[0]["mock-action"]["href"], json!(href));
+        if response[0]["mock-action"]["status"] == json!("pending") {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let (status, response) = gateway
+        .get::<Value>("/things/mock-device/actions/mock-action")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response[0]["mock-action"]["status"], json!("pending"));
+
+    let (status, _) = gateway.delete::<String>(&href).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device/actions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
+
+#[tokio::test]
+#[serial]
+async fn get_events_empty() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let (status, response) = gateway.get::<Value>("/things/mock-device/events").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+
+    let (status, response) = gateway
+        .get::<Value>("/things/mock-device/events/mock-event")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, json!([]));
+}
@@ -0,0 +1,309 @@
+use futures::{SinkExt, StreamExt};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serial_test::serial;
+use test_utils::{
+    gateway::Gateway,
+    mock_thing::{self, DeviceExt},
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Error, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+async fn receive_json(socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> Value {
+    loop {
+        match socket.next().await.expect("Receive message").unwrap() {
+            Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn malformed_message() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    socket
+        .send(Message::Text("not json".to_owned()))
+        .await
+        .unwrap();
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("error"));
+    assert_eq!(response["data"]["status"], json!("400 Bad Request"));
+}
+
+#[tokio::test]
+#[serial]
+async fn set_property_without_thing_id() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    socket
+        .send(Message::Text(
+            json!({"messageType": "setProperty", "data": {"mock-property": 42}}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("error"));
+    assert_eq!(response["data"]["status"], json!("400 Bad Request"));
+}
+
+#[tokio::test]
+#[serial]
+async fn set_property_on_thing_socket() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+    let mut socket = gateway.connect_things_socket("/things/mock-device").await;
+
+    socket
+        .send(Message::Text(
+            json!({"messageType": "setProperty", "data": {"mock-property": 42}}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = receive_json(&mut socket).await;
+    assert_eq!(
+        response,
+        json!({
+            "messageType": "propertyStatus",
+            "id": "mock-device",
+            "data": {"mock-property": 42}
+        })
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn set_invalid_property_value() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+    let mut socket = gateway.connect_things_socket("/things/mock-device").await;
+
+    socket
+        .send(Message::Text(
+            json!({"messageType": "setProperty", "data": {"mock-property": "42"}}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("error"));
+    assert_eq!(response["data"]["status"], json!("400 Bad Request"));
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_to_property() {
+    let mut device = mock_thing::device("mock-device");
+    device.add_property(mock_thing::property("ignored-property", "integer"));
+    device.add_property(mock_thing::property("mock-property", "integer"));
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon.create_mock_device(device).await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    socket
+        .send(Message::Text(
+            json!({"messageType": "subscribe", "id": "mock-device", "data": ["mock-property"]})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    for (name, value) in [("ignored-property", 1), ("mock-property", 42)] {
+        socket
+            .send(Message::Text(
+                json!({"messageType": "setProperty", "id": "mock-device", "data": {name: value}})
+                    .to_string(),
+            ))
+            .await
+            .unwrap();
+    }
+    let response = receive_json(&mut socket).await;
+    assert_eq!(
+        response,
+        json!({
+            "messageType": "propertyStatus",
+            "id": "mock-device",
+            "data": {"mock-property": 42}
+        })
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn thing_lifecycle_notifications() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    gateway
+        .post::<String>(
+            "/things",
+            json!({"id": "mock-device", "title": "Mock Device"}),
+        )
+        .await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("thingAdded"));
+    assert_eq!(response["id"], json!("mock-device"));
+    assert_eq!(response["data"]["title"], json!("Mock Device"));
+
+    gateway
+        .patch::<Value>("/things/mock-device", json!({"title": "Renamed Device"}))
+        .await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("thingModified"));
+    assert_eq!(response["data"]["title"], json!("Renamed Device"));
+
+    gateway.delete::<Value>("/things/mock-device").await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(
+        response,
+        json!({"messageType": "thingRemoved", "id": "mock-device"})
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn thing_available_notification() {
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    addon
+        .create_mock_device(mock_thing::device("mock-device"))
+        .await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("thingAvailable"));
+    assert_eq!(response["id"], json!("mock-device"));
+}
+
+#[tokio::test]
+#[serial]
+async fn new_things_socket() {
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon
+        .create_mock_device(mock_thing::device("first-device"))
+        .await;
+    let mut socket = gateway.connect_things_socket("/new_things").await;
+
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("thingAvailable"));
+    assert_eq!(response["id"], json!("first-device"));
+
+    addon
+        .create_mock_device(mock_thing::device("second-device"))
+        .await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("thingAvailable"));
+    assert_eq!(response["id"], json!("second-device"));
+}
+
+#[tokio::test]
+#[serial]
+async fn connect_without_token() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    match connect_async("ws://127.0.0.1:8082/things").await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("Unexpected handshake result: {:?}", other.map(|_| ())),
+    }
+    match connect_async("ws://127.0.0.1:8082/things?jwt=invalid").await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("Unexpected handshake result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn connect_with_authorization_header() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let mut request = "ws://127.0.0.1:8082/things".into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Authorization",
+        format!("Bearer {}", gateway.jwt.clone().unwrap())
+            .parse()
+            .unwrap(),
+    );
+    assert!(connect_async(request).await.is_ok());
+}
+
+#[tokio::test]
+#[serial]
+async fn connect_from_foreign_origin() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+
+    let url = format!(
+        "ws://127.0.0.1:8082/things?jwt={}",
+        gateway.jwt.clone().unwrap()
+    );
+    let mut request = url.into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Origin", "https://evil.example".parse().unwrap());
+    match connect_async(request).await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 403),
+        other => panic!("Unexpected handshake result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn disconnect_things_of_stopped_addon() {
+    let (gateway, mut addon) = Gateway::startup_with_mock_addon().await;
+    addon
+        .create_mock_device(mock_thing::device("mock-device"))
+        .await;
+    let mut socket = gateway.connect_things_socket("/things/mock-device").await;
+
+    gateway
+        .put::<String>("/addons/mock-addon", json!({"enabled": false}))
+        .await;
+    let response = receive_json(&mut socket).await;
+    assert_eq!(
+        response,
+        json!({"messageType": "connected", "id": "mock-device", "data": false})
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn addon_job_notifications() {
+    let mut gateway = Gateway::startup().await;
+    gateway.authorize().await;
+    let mut socket = gateway.connect_things_socket("/things").await;
+
+    let (status, job) = gateway
+        .post::<Value>(
+            "/addons",
+            json!({"id": "mock-addon", "url": "http://127.0.0.1:1/mock-addon.tar.gz", "checksum": ""}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(job["status"], json!("downloading"));
+
+    let response = receive_json(&mut socket).await;
+    assert_eq!(response["messageType"], json!("addonJob"));
+    assert_eq!(response["data"]["id"], job["id"]);
+    assert_eq!(response["data"]["addonId"], json!("mock-addon"));
+    assert_eq!(response["data"]["status"], json!("failed"));
+
+    let (status, response) = gateway
+        .get::<Value>(&format!("/addons/jobs/{}", job["id"]))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["status"], json!("failed"));
+    assert!(response["error"].is_string());
+}